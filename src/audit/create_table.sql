@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS requests (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  run_id TEXT NOT NULL,
+  service TEXT NOT NULL,
+  timestamp INTEGER NOT NULL,
+  method TEXT NOT NULL,
+  url TEXT NOT NULL,
+  request_headers TEXT NOT NULL,
+  -- gzipped
+  request_body BLOB,
+  status INTEGER,
+  response_headers TEXT,
+  -- gzipped
+  response_body BLOB,
+  error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS requests_run_id_url ON requests (run_id, url);
@@ -0,0 +1,260 @@
+use std::{
+    path::Path,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use reqwest::{header::HeaderMap, Request, Url};
+use rusqlite::named_params;
+use serde_json::{Map, Value};
+
+use crate::{
+    http_client::{HttpResponse, Service},
+    snapshot::compress,
+};
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// Headers whose values are never written to the audit log
+const SECRET_HEADERS: &[&str] = &["authorization", "cookie", "x-api-key", "x-goog-api-key"];
+
+/// Query parameters whose values are never written to the audit log
+const SECRET_QUERY_PARAMS: &[&str] = &["key", "api_key", "access_token"];
+
+const REDACTED: &str = "REDACTED";
+
+/// An opt-in log of every request sent to an external API, along with the response we got back
+///
+/// Every entry is tagged with the ID of the run that produced it, so the responses behind a
+/// particular output file can be found later. Request and response bodies are stored gzipped, the
+/// same as in the page archive
+pub struct AuditLog {
+    pool: Pool<SqliteConnectionManager>,
+    run_id: String,
+}
+
+impl AuditLog {
+    /// Start recording requests to the SQLite database at the given path
+    ///
+    /// Until this is called, nothing is recorded
+    pub fn enable(path: &Path) -> Result<&'static Self> {
+        let pool = Pool::new(SqliteConnectionManager::file(path))?;
+        let sql = include_str!("./create_table.sql");
+        pool.get()?.execute_batch(sql)?;
+
+        let run_id = new_run_id();
+        tracing::info!("recording requests for run {run_id} to {}", path.display());
+
+        let log = Self { pool, run_id };
+        AUDIT_LOG
+            .set(log)
+            .map_err(|_| eyre!("the audit log has already been enabled"))?;
+
+        Ok(AUDIT_LOG.get().unwrap())
+    }
+
+    /// Get a handle to the global audit log, if it has been enabled
+    pub fn get() -> Option<&'static Self> {
+        AUDIT_LOG.get()
+    }
+
+    /// The ID used to tag every request made by this run
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Store a request along with its response, or the error that prevented a response
+    pub(crate) async fn record(
+        &self,
+        request: AuditRequest,
+        response: &Result<HttpResponse>,
+    ) -> Result<()> {
+        let conn = self.pool.get()?;
+        let run_id = self.run_id.clone();
+
+        let (status, response_headers, response_body, error) = match response {
+            Ok(response) => (
+                Some(response.status.as_u16()),
+                Some(redact_headers(&response.headers).to_string()),
+                Some(response.body.clone()),
+                None,
+            ),
+            Err(e) => (None, None, None, Some(e.to_string())),
+        };
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let request_body = request.body.as_deref().map(compress).transpose()?;
+            let response_body = response_body.as_deref().map(compress).transpose()?;
+
+            let sql = "INSERT INTO requests (run_id, service, timestamp, method, url, \
+                request_headers, request_body, status, response_headers, response_body, error) \
+                VALUES (:run_id, :service, :timestamp, :method, :url, :request_headers, \
+                :request_body, :status, :response_headers, :response_body, :error)";
+            let mut statement = conn.prepare_cached(sql)?;
+            statement.execute(named_params! {
+                ":run_id": run_id,
+                ":service": request.service.to_str(),
+                ":timestamp": request.timestamp,
+                ":method": request.method,
+                ":url": request.url,
+                ":request_headers": request.headers,
+                ":request_body": request_body,
+                ":status": status,
+                ":response_headers": response_headers,
+                ":response_body": response_body,
+                ":error": error,
+            })?;
+
+            Ok(())
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+/// The parts of a request that are written to the audit log, with any secrets removed
+pub(crate) struct AuditRequest {
+    service: Service,
+    timestamp: i64,
+    method: String,
+    url: String,
+    headers: String,
+    body: Option<Vec<u8>>,
+}
+
+impl AuditRequest {
+    pub fn new(service: Service, request: &Request) -> Self {
+        Self {
+            service,
+            timestamp: unix_millis(),
+            method: request.method().to_string(),
+            url: redact_url(request.url()),
+            headers: redact_headers(request.headers()).to_string(),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(<[u8]>::to_vec),
+        }
+    }
+}
+
+/// Replace the values of any query parameters that might contain credentials
+pub(crate) fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| {
+                let value = if SECRET_QUERY_PARAMS.contains(&name.as_ref()) {
+                    REDACTED.to_string()
+                } else {
+                    value.into_owned()
+                };
+
+                (name.into_owned(), value)
+            })
+            .collect();
+
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    url.to_string()
+}
+
+/// Convert a set of headers to a JSON object, replacing the values of any that might contain
+/// credentials
+pub(crate) fn redact_headers(headers: &HeaderMap) -> Value {
+    let mut map = Map::new();
+
+    for (name, value) in headers {
+        let value = if SECRET_HEADERS.contains(&name.as_str()) {
+            REDACTED.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+
+        map.insert(name.to_string(), Value::String(value));
+    }
+
+    Value::Object(map)
+}
+
+/// An ID that is unique to this process, made from the start time and process ID
+fn new_run_id() -> String {
+    format!("{}-{}", unix_millis(), std::process::id())
+}
+
+pub(crate) fn unix_millis() -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    now.as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{
+        header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+        Method, StatusCode,
+    };
+
+    use super::*;
+    use crate::snapshot::decompress;
+
+    #[tokio::test]
+    async fn stores_bodies_gzipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = Pool::new(SqliteConnectionManager::file(dir.path().join("audit.db"))).unwrap();
+        pool.get()
+            .unwrap()
+            .execute_batch(include_str!("./create_table.sql"))
+            .unwrap();
+        let log = AuditLog {
+            pool,
+            run_id: new_run_id(),
+        };
+
+        let url = Url::parse("https://example.com/translate").unwrap();
+        let mut request = Request::new(Method::POST, url);
+        *request.body_mut() = Some("原文".into());
+        let response = HttpResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: "translation".into(),
+        };
+
+        let request = AuditRequest::new(Service::GoogleTranslate, &request);
+        log.record(request, &Ok(response)).await.unwrap();
+
+        let conn = log.pool.get().unwrap();
+        let (request_body, response_body): (Vec<u8>, Vec<u8>) = conn
+            .query_row(
+                "SELECT request_body, response_body FROM requests",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(decompress(&request_body).unwrap(), "原文".as_bytes());
+        assert_eq!(decompress(&response_body).unwrap(), b"translation");
+    }
+
+    #[test]
+    fn redacts_api_keys() {
+        let url = Url::parse("https://example.com/graph?terms=foo&key=secret&geo=GB").unwrap();
+        assert_eq!(
+            redact_url(&url),
+            "https://example.com/graph?terms=foo&key=REDACTED&geo=GB"
+        );
+
+        let headers = HeaderMap::from_iter([
+            (AUTHORIZATION, HeaderValue::from_static("Bearer secret")),
+            (CONTENT_TYPE, HeaderValue::from_static("application/json")),
+        ]);
+        let redacted = redact_headers(&headers);
+        assert_eq!(redacted["authorization"], "REDACTED");
+        assert_eq!(redacted["content-type"], "application/json");
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 
 use clap::Parser;
 use color_eyre::Result;
-use polars::{lazy::dsl::col, prelude::*};
+use polars::{
+    lazy::dsl::{col, count},
+    prelude::*,
+};
 use statrs::distribution::{ContinuousCDF, Normal};
 
 #[derive(Debug, Parser)]
@@ -35,9 +38,10 @@ fn main() -> Result<()> {
         "british_english_seo_uk_score",
     ];
 
+    // only drop rows missing a score, as columns like `author` are often empty
     let scores = df
+        .clone()
         .lazy()
-        .drop_nulls(None)
         .select(columns.map(col))
         .collect()?
         .drop_nulls::<String>(None)?;
@@ -51,6 +55,16 @@ fn main() -> Result<()> {
     println!("{mean_and_std}");
     println!("rows: {num_rows}");
 
+    // URL lists from a structured topics file label each page with its topic's category
+    if df.get_column_names().contains(&"category") {
+        let mut by_category = mean_by_category(df, &columns)?;
+
+        let mut file = File::create("analysis_by_category.csv")?;
+        CsvWriter::new(&mut file).finish(&mut by_category)?;
+
+        println!("{by_category}");
+    }
+
     for col1 in columns {
         for col2 in columns {
             if col1 == col2 {
@@ -104,10 +118,32 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// The number of rows and the mean of each score, for each category of topic
+///
+/// Rows without a category are left out
+fn mean_by_category(df: DataFrame, columns: &[&str]) -> Result<DataFrame> {
+    let selected: Vec<_> = ["category"].iter().chain(columns).map(|c| col(c)).collect();
+    let means: Vec<_> = [count().alias("rows")]
+        .into_iter()
+        .chain(columns.iter().map(|c| col(c).mean()))
+        .collect();
+
+    let by_category = df
+        .lazy()
+        .select(selected)
+        .drop_nulls(None)
+        .groupby([col("category")])
+        .agg(means)
+        .sort("category", SortOptions::default())
+        .collect()?;
+
+    Ok(by_category)
+}
+
 /// Convert a z-score to a p-value by using the cumulative distribution function for the standard
 /// normal distribution (the normal distribution with mean 0 and S.D. 1)
 fn p_value_from_z_score(z_score: f64) -> f64 {
-    let z = -1.0 * z_score.abs();
+    let z = -z_score.abs();
     Normal::new(0.0, 1.0).unwrap().cdf(z)
 }
 
@@ -1,11 +1,24 @@
-use clap::Parser;
-use color_eyre::{eyre::Context, Help, Result};
+use chrono::NaiveDate;
+use clap::{Parser, ValueEnum};
+use color_eyre::{
+    eyre::{bail, Context},
+    Help, Result,
+};
 use dissertation::{
-    html,
-    input::{self, ChatgptPrompts},
+    audit::AuditLog,
+    cassette::{Cassette, CassetteMiss, CassetteMode},
+    config::Config,
+    dates::{self, DateFilter, Filtered},
+    duplicates,
+    html::{Article, ArticleMetadata, SelectorProfile},
+    input::{self, ChatgptPrompts, UrlEntry},
+    language::{self, Language},
     output,
-    scoring::{self, Trends},
-    translate,
+    scoring::{self, TranslationScores, Trends},
+    script::Script,
+    snapshot::{DescriptionVersion, SnapshotStore},
+    sources::{Outlet, Sources},
+    translate::{self, Translations},
 };
 use futures::future::try_join_all;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
@@ -20,6 +33,9 @@ use std::{
 #[derive(Debug, Parser)]
 pub struct Args {
     /// The path to the list of URLs
+    ///
+    /// Each line is a URL, optionally followed by a tab and the date the page was published (as
+    /// written by `scrape_bbc` and `scrape_feeds`)
     #[clap(long, short)]
     pub urls: PathBuf,
 
@@ -38,14 +54,145 @@ pub struct Args {
     /// Optional limit for the number of URLs to process
     #[clap(long, short)]
     pub limit: Option<usize>,
+
+    /// Optional path to a config file, containing settings such as rate limits
+    #[clap(long, short)]
+    pub config: Option<PathBuf>,
+
+    /// The news outlet for URLs that aren't on a known outlet's site, `bbc` (BBC Chinese) or
+    /// `voa` (VOA Chinese)
+    ///
+    /// Every other URL is read with the selectors of the outlet whose site it is on
+    #[clap(long, default_value = "bbc")]
+    pub source: Outlet,
+
+    /// Optional path to a selector profile for the `--source` outlet, describing where to find
+    /// the description on a page (defaults to the outlet's built-in profile)
+    #[clap(long, short)]
+    pub selectors: Option<PathBuf>,
+
+    /// Optional path to a SQLite database where every request and response is recorded (with
+    /// secrets redacted)
+    #[clap(long)]
+    pub audit_log: Option<PathBuf>,
+
+    /// Optional path to a cassette file, used to record every HTTP interaction, or to replay them
+    /// without touching the network
+    #[clap(long, requires = "cassette_mode")]
+    pub cassette: Option<PathBuf>,
+
+    /// Whether to record to, or replay from, the cassette
+    #[clap(long, value_enum, requires = "cassette")]
+    pub cassette_mode: Option<CassetteMode>,
+
+    /// Optional path to a SQLite archive where every fetched page is saved
+    #[clap(long)]
+    pub snapshots: Option<PathBuf>,
+
+    /// Read pages from the snapshot archive instead of fetching them, using the latest snapshot
+    /// of each page
+    #[clap(long, requires = "snapshots")]
+    pub offline: bool,
+
+    /// Which text from each page to translate and score
+    ///
+    /// `paragraph` gives a row for every heading, paragraph and caption in the article's body,
+    /// numbered by its position in the article
+    #[clap(long, value_enum, default_value_t = Unit::Description)]
+    pub unit: Unit,
+
+    /// Convert every text into one script before translating it, using an offline
+    /// character-by-character conversion table
+    ///
+    /// The script each text was written in on the page is still written to the `script` column
+    #[clap(long, value_enum)]
+    pub convert_script: Option<Script>,
+
+    /// The language the pages are written in
+    ///
+    /// Texts identified as being in some other language (such as English pages, or pages that are
+    /// mostly English) are written to the output without being translated
+    #[clap(long, value_enum, default_value_t = Language::Chinese)]
+    pub language: Language,
+
+    /// Only score pages published on or after this day (`YYYY-MM-DD`)
+    ///
+    /// A page's date is read from the page itself, or else from the URL list. Pages without a
+    /// known date are left out whenever `--since`, `--until` or `--per-month` is used
+    #[clap(long)]
+    pub since: Option<NaiveDate>,
+
+    /// Only score pages published on or before this day (`YYYY-MM-DD`)
+    #[clap(long)]
+    pub until: Option<NaiveDate>,
+
+    /// Optional limit for the number of pages to score from each calendar month, picked evenly
+    /// from across the month, so that each month is equally represented
+    #[clap(long)]
+    pub per_month: Option<usize>,
+
+    /// How similar two pages' descriptions have to be to count as near-duplicates, from 0 to 1
+    ///
+    /// This is the share of the runs of three characters in either description that are in both
+    #[clap(long, default_value_t = 0.8)]
+    pub duplicate_similarity: f64,
+
+    /// Optional path to a CSV report of the clusters of pages with near-duplicate descriptions
+    #[clap(long)]
+    pub duplicates: Option<PathBuf>,
+
+    /// Only translate and score the first page of each cluster of near-duplicates (in the order
+    /// of the URL list), leaving the rest out of the output
+    #[clap(long)]
+    pub drop_duplicates: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Unit {
+    Description,
+    Headline,
+    Paragraph,
+}
+
+impl Unit {
+    fn to_str(self) -> &'static str {
+        match self {
+            Self::Description => "description",
+            Self::Headline => "headline",
+            Self::Paragraph => "paragraph",
+        }
+    }
+
+    /// The pieces of text from an article to translate, and what kind of text each one is
+    fn texts(self, article: &Article) -> Vec<(&'static str, String)> {
+        let metadata = &article.metadata;
+
+        match self {
+            Self::Description => metadata
+                .description
+                .iter()
+                .map(|s| ("description", s.clone()))
+                .collect(),
+            Self::Headline => metadata
+                .headline
+                .iter()
+                .map(|s| ("headline", s.clone()))
+                .collect(),
+            Self::Paragraph => article
+                .content
+                .iter()
+                .map(|block| (block.kind.to_str(), block.text.clone()))
+                .collect(),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
-    dotenvy::dotenv()
-        .context("no .env file found")
-        .suggestion("create a file at `./.env` containing the required env vars")?;
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
     let Args {
         urls,
@@ -53,9 +200,68 @@ async fn main() -> Result<()> {
         function_words,
         output,
         limit,
+        config,
+        source,
+        selectors,
+        audit_log,
+        cassette,
+        cassette_mode,
+        snapshots,
+        offline,
+        unit,
+        convert_script,
+        language,
+        since,
+        until,
+        per_month,
+        duplicate_similarity,
+        duplicates,
+        drop_duplicates,
     } = Args::parse();
 
-    let urls = input::read_file_lines(urls)?;
+    if convert_script.is_some() && language != Language::Chinese {
+        bail!(
+            "--convert-script only applies to Chinese, not {}",
+            language.name()
+        );
+    }
+
+    if !(0.0..=1.0).contains(&duplicate_similarity) {
+        bail!("--duplicate-similarity must be between 0 and 1, not {duplicate_similarity}");
+    }
+
+    // credentials aren't needed when replaying a cassette
+    let env = dotenvy::dotenv();
+    if cassette_mode != Some(CassetteMode::Replay) {
+        env.context("no .env file found")
+            .suggestion("create a file at `./.env` containing the required env vars")?;
+    }
+
+    if let (Some(path), Some(mode)) = (cassette, cassette_mode) {
+        Cassette::enable(&path, mode)?;
+    }
+
+    if let Some(path) = snapshots {
+        SnapshotStore::enable(&path, offline)?;
+    }
+
+    let config = match config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    config.apply()?;
+
+    let profile = match selectors {
+        Some(path) => Some(SelectorProfile::from_file(path)?),
+        None => None,
+    };
+    let sources = Sources::new(source, profile);
+
+    if let Some(path) = audit_log {
+        AuditLog::enable(&path)?;
+    }
+
+    let urls = input::read_url_list(urls)?;
     let function_words = match function_words {
         Some(path) => input::read_file_lines(path)?,
         None => vec![],
@@ -65,47 +271,354 @@ async fn main() -> Result<()> {
 
     let out = make_output(&output)?;
 
+    let urls: Vec<_> = urls.into_iter().take(limit.unwrap_or(usize::MAX)).collect();
     let progress = Progress::new(urls.len());
-    let trends = Trends::new();
 
-    let urls = urls
+    let pipeline = Pipeline {
+        unit,
+        language,
+        convert_script,
+        function_words,
+        trends: Trends::new(),
+        prompts,
+        sources,
+    };
+
+    // every page is fetched before any are translated, so pages can be picked by the date they
+    // were published, which is often only on the page itself
+    let pages = urls
+        .into_iter()
+        .map(|entry| pipeline.fetch_page(&progress, entry));
+    let pages = try_join_all(pages).await?;
+
+    // descriptions are often edited after an article is published, so it matters which version
+    // was translated
+    let changed: Vec<_> = pages
+        .iter()
+        .filter(|page| {
+            let version = page.description_version.as_ref();
+            version.is_some_and(|version| version.previous.is_some())
+        })
+        .collect();
+
+    if !changed.is_empty() {
+        eprintln!(
+            "{} of {} pages had a different description from when they were last fetched:",
+            changed.len(),
+            pages.len()
+        );
+
+        for page in changed {
+            let version = page.description_version.as_ref().unwrap().version;
+            eprintln!("  {} (now version {version})", page.entry.url);
+        }
+    }
+
+    let filter = DateFilter {
+        since,
+        until,
+        per_month,
+    };
+    let total = pages.len();
+    let Filtered {
+        kept: pages,
+        undated,
+    } = filter.apply(pages, |page| {
+        page.entry.published.as_deref().and_then(dates::parse)
+    });
+
+    if !undated.is_empty() {
+        eprintln!(
+            "{} of {total} pages had no publication date, so were left out:",
+            undated.len()
+        );
+
+        for page in undated {
+            eprintln!("  {}", page.entry.url);
+        }
+    }
+
+    // BBC reuses much the same description across live pages and the articles that follow them
+    // up, which would otherwise be scored as if they were unrelated
+    let descriptions: Vec<_> = pages
+        .iter()
+        .map(|page| page.article.as_ref()?.metadata.description.as_deref())
+        .collect();
+    let clusters = duplicates::clusters(&descriptions, duplicate_similarity);
+
+    if !clusters.is_empty() {
+        let in_clusters: usize = clusters.iter().map(Vec::len).sum();
+        eprintln!(
+            "{in_clusters} of {} pages had near-duplicate descriptions, in {} clusters:",
+            pages.len(),
+            clusters.len()
+        );
+
+        for (i, cluster) in clusters.iter().enumerate() {
+            eprintln!("  cluster {}:", i + 1);
+            for &page in cluster {
+                eprintln!("    {}", pages[page].entry.url);
+            }
+        }
+    }
+
+    if let Some(path) = duplicates {
+        let report: Vec<Vec<_>> = clusters
+            .iter()
+            .map(|cluster| {
+                cluster
+                    .iter()
+                    .map(|&page| {
+                        let description = descriptions[page].unwrap_or_default();
+                        (pages[page].entry.url.as_str(), description)
+                    })
+                    .collect()
+            })
+            .collect();
+        output::write_duplicates(make_output(&path)?, &report)?;
+    }
+
+    let pages = if drop_duplicates {
+        let dropped: HashSet<_> = clusters.iter().flat_map(|cluster| &cluster[1..]).collect();
+        pages
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !dropped.contains(i))
+            .map(|(_, page)| page)
+            .collect()
+    } else {
+        pages
+    };
+
+    progress.translations.set_length(pages.len() as u64);
+    progress.scores.set_length(pages.len() as u64);
+
+    let pages = pages
         .into_iter()
-        .take(limit.unwrap_or(usize::MAX))
-        .map(|url| process_row(&progress, url, &function_words, &trends, &prompts));
+        .map(|page| pipeline.process_page(&progress, page));
+
+    let pages = try_join_all(pages).await?;
+    drop(progress);
+
+    let missing: Vec<_> = pages
+        .iter()
+        .filter_map(|rows| match rows.as_slice() {
+            [row] if row.unit.is_none() => Some(&row.url),
+            _ => None,
+        })
+        .collect();
 
-    let rows = try_join_all(urls).await?;
+    if !missing.is_empty() {
+        let unit = unit.to_str();
+        eprintln!("{} of {} pages had no {unit}:", missing.len(), pages.len());
+
+        for url in missing {
+            eprintln!("  {url}");
+        }
+    }
+
+    let rows: Vec<_> = pages.into_iter().flatten().collect();
+
+    let other_languages: Vec<_> = rows
+        .iter()
+        .filter_map(|row| Some((&row.url, row.language?)))
+        .filter(|(_, lang)| !language.is(*lang))
+        .collect();
+
+    if !other_languages.is_empty() {
+        let name = language.name();
+        let texts = rows.iter().filter(|row| row.unit.is_some()).count();
+        eprintln!(
+            "{} of {texts} texts weren't in {name}, so weren't translated:",
+            other_languages.len()
+        );
+
+        for (url, lang) in other_languages {
+            eprintln!("  {url} ({})", lang.eng_name());
+        }
+    }
 
     tracing::info!("writing output to {}", output.to_string_lossy());
-    output::write_csv(&prompts, out, &rows)?;
+    output::write_csv(&pipeline.prompts, out, &rows)?;
 
     Ok(())
 }
 
-async fn process_row(
-    progress: &Progress,
-    url: String,
-    function_words: &HashSet<String>,
-    trends: &Trends,
-    prompts: &ChatgptPrompts,
-) -> Result<CsvRow> {
-    let Ok(chinese_description)= html::description_of_page(&url).await else {
-        return Ok(CsvRow::new(url, None, None));
-    };
-    progress.descriptions.inc(1);
+/// Everything needed to turn a page into rows of the output, which stays the same for the whole
+/// run
+struct Pipeline {
+    unit: Unit,
+    language: Language,
+    /// The script to convert each text into before it is translated, if any
+    convert_script: Option<Script>,
+    function_words: HashSet<String>,
+    trends: Trends,
+    prompts: ChatgptPrompts,
+    sources: Sources,
+}
 
-    let Ok(translations) = translate::translate(&chinese_description, prompts).await else {
-        return Ok(CsvRow::new(url, None, None));
-    };
-    progress.translations.inc(1);
+impl Pipeline {
+    /// Fetch a page, and work out when it was published
+    ///
+    /// The date on the page is used if it has one, and otherwise the date from the URL list. If
+    /// pages are being saved to a snapshot archive, the page's description is added to its history
+    /// there
+    async fn fetch_page(&self, progress: &Progress, mut entry: UrlEntry) -> Result<Page> {
+        let source = self.sources.for_url(&entry.url);
 
-    let Ok(scores) = scoring::score_translations(trends, &translations, function_words).await else {
-        return Ok(CsvRow::new(url, Some(translations), None));
-    };
-    progress.scores.inc(1);
+        let article = match source.article(&entry.url).await {
+            Ok(article) => article,
+            // a replayed run that has diverged from the recorded one can't be trusted at all
+            Err(e) if e.is::<CassetteMiss>() => return Err(e),
+            Err(_) => {
+                return Ok(Page {
+                    entry,
+                    article: None,
+                    description_version: None,
+                })
+            }
+        };
+        progress.pages.inc(1);
+
+        entry.published = article.metadata.published.clone().or(entry.published);
+
+        let description_version = match (SnapshotStore::get(), &article.metadata.description) {
+            (Some(store), Some(description)) if !SnapshotStore::is_offline() => {
+                Some(store.record_description(&entry.url, description).await?)
+            }
+            _ => None,
+        };
+
+        Ok(Page {
+            entry,
+            article: Some(article),
+            description_version,
+        })
+    }
+
+    /// Translate and score each piece of text from a page
+    ///
+    /// A page without any text to translate (or that failed to load) still gets a row, holding
+    /// whatever else we know about it
+    async fn process_page(&self, progress: &Progress, page: Page) -> Result<Vec<CsvRow>> {
+        let Page {
+            entry,
+            article,
+            description_version,
+        } = page;
+        let description_version = description_version.map(|description| description.version);
+        let UrlEntry {
+            url,
+            published,
+            topic,
+            category,
+        } = entry;
+        let name = self.sources.for_url(&url).outlet().to_str();
+
+        let Some(article) = article else {
+            // keep the publication date from the URL list, even if the page is gone
+            let metadata = ArticleMetadata {
+                published,
+                ..Default::default()
+            };
+            let row = CsvRow::new(name, url, metadata, None, None).with_topic(topic, category);
+            return Ok(vec![row]);
+        };
+
+        let mut metadata = article.metadata.clone();
+        metadata.published = published;
 
-    let row = CsvRow::new(url, Some(translations), Some(scores));
+        let texts = self.unit.texts(&article);
+        if texts.is_empty() {
+            let row = CsvRow::new(name, url, metadata, None, None)
+                .with_topic(topic, category)
+                .with_description_version(description_version);
+            return Ok(vec![row]);
+        }
+
+        // the bars start with one piece of text per page
+        let extra = texts.len() as u64 - 1;
+        progress.translations.inc_length(extra);
+        progress.scores.inc_length(extra);
+
+        let rows = texts.into_iter().enumerate().map(|(index, (kind, text))| {
+            let url = url.clone();
+            let metadata = metadata.clone();
+            let (topic, category) = (topic.clone(), category.clone());
+
+            async move {
+                let language = language::identify(&text);
+                let script = match self.language {
+                    Language::Chinese => Script::detect(&text),
+                    _ => None,
+                };
+
+                // don't translate text in some other language as if it were in this one
+                let (translations, scores) = if language.is_some_and(|lang| !self.language.is(lang))
+                {
+                    (None, None)
+                } else {
+                    self.process_text(progress, &text, script).await?
+                };
+
+                let row = CsvRow::new(name, url, metadata, translations, scores)
+                    .with_topic(topic, category)
+                    .with_description_version(description_version)
+                    .with_unit(kind, index)
+                    .with_script(script)
+                    .with_language(language);
+                Ok(row)
+            }
+        });
+
+        try_join_all(rows).await
+    }
+
+    /// Translate and score a piece of text, which (for Chinese) is written in the given script
+    ///
+    /// If `convert_script` is set, the text is converted into that script first, and the converted
+    /// text is what ends up in the output. A translation or score that fails is left out, unless
+    /// it's because a replayed cassette is missing the request, which fails the whole run
+    async fn process_text(
+        &self,
+        progress: &Progress,
+        source_text: &str,
+        script: Option<Script>,
+    ) -> Result<(Option<Translations>, Option<TranslationScores>)> {
+        let (source_text, script) = match self.convert_script {
+            Some(target) => (target.convert(source_text), Some(target)),
+            None => (source_text.to_string(), script),
+        };
+
+        let translations =
+            translate::translate(&source_text, self.language, script, &self.prompts).await;
+        let translations = match translations {
+            Ok(translations) => translations,
+            Err(e) if e.is::<CassetteMiss>() => return Err(e),
+            Err(_) => return Ok((None, None)),
+        };
+        progress.translations.inc(1);
+
+        let scores =
+            scoring::score_translations(&self.trends, &translations, &self.function_words).await;
+        let scores = match scores {
+            Ok(scores) => scores,
+            Err(e) if e.is::<CassetteMiss>() => return Err(e),
+            Err(_) => return Ok((Some(translations), None)),
+        };
+        progress.scores.inc(1);
+
+        Ok((Some(translations), Some(scores)))
+    }
+}
 
-    Ok(row)
+/// A fetched page, with the date it was published (if known) in its entry
+struct Page {
+    entry: UrlEntry,
+    /// The article, or `None` if the page failed to load
+    article: Option<Article>,
+    /// Which version of its description the page has, if it's being saved to a snapshot archive
+    description_version: Option<DescriptionVersion>,
 }
 
 fn make_output(path: &Path) -> Result<impl Write, std::io::Error> {
@@ -115,7 +628,7 @@ fn make_output(path: &Path) -> Result<impl Write, std::io::Error> {
 
 struct Progress {
     _multi: MultiProgress,
-    descriptions: ProgressBar,
+    pages: ProgressBar,
     translations: ProgressBar,
     scores: ProgressBar,
 }
@@ -127,18 +640,18 @@ impl Progress {
         let count = count as u64;
         let multi = MultiProgress::new();
 
-        let descriptions = multi.insert(
+        let pages = multi.insert(
             0,
             ProgressBar::new(count)
                 .with_style(sty.clone())
-                .with_message("Getting descriptions"),
+                .with_message("Fetching pages"),
         );
 
         let translations = multi.insert(
             1,
             ProgressBar::new(count)
                 .with_style(sty.clone())
-                .with_message("Translating descriptions"),
+                .with_message("Translating text"),
         );
 
         let scores = multi.insert(
@@ -152,7 +665,7 @@ impl Progress {
 
         Self {
             _multi: multi,
-            descriptions,
+            pages,
             translations,
             scores,
         }
@@ -161,7 +674,7 @@ impl Progress {
 
 impl Drop for Progress {
     fn drop(&mut self) {
-        self.descriptions.finish();
+        self.pages.finish();
         self.translations.finish();
         self.scores.finish();
     }
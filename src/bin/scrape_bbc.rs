@@ -1,55 +1,633 @@
-use std::collections::HashSet;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
 use std::path::Path;
+use std::time::Duration;
 use std::{fs::File, io::BufWriter, path::PathBuf};
 
-use clap::Parser;
-use color_eyre::{eyre::eyre, Result};
-use itertools::Itertools;
-use tl::ParserOptions;
+use chrono::NaiveDate;
+use clap::{Parser, ValueEnum};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Report, Result,
+};
+use dissertation::{
+    config::Config,
+    dates::{self, DateFilter, Filtered},
+    html::SelectorProfile,
+    input::UrlEntry,
+    output,
+    seen::SeenStore,
+    sources::{NewsSource, Outlet},
+    urls,
+};
+use futures::future::join_all;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::sync::Semaphore;
 
 #[derive(Debug, Parser)]
 pub struct Args {
-    /// The path to the file containing the BBC topic URLs
+    /// The path to the file containing the topic URLs
+    ///
+    /// Each line is a topic URL, optionally followed by the number of pages to scrape. If the
+    /// number of pages is left out, it is read from the topic's pagination control, or pages are
+    /// followed until one has no new articles.
+    ///
+    /// A `.json5` file instead lists each topic's `url`, and optionally its `name`, `category`,
+    /// `pages`, and `selectors` (a selector profile for just that topic). The name and category
+    /// are written after each of the topic's articles
     #[clap(long, short)]
     pub topics: PathBuf,
 
     /// The path to the output URL file
     #[clap(long, short)]
     pub output: PathBuf,
+
+    /// The maximum number of topic pages to fetch at once
+    #[clap(long, default_value_t = 8)]
+    pub concurrency: usize,
+
+    /// The number of times to retry a page that fails to load
+    #[clap(long, default_value_t = 3)]
+    pub retries: u32,
+
+    /// Optional limit for the number of pages to scrape from each topic
+    #[clap(long)]
+    pub max_pages: Option<usize>,
+
+    /// Optional path to a config file, containing settings such as rate limits
+    #[clap(long, short)]
+    pub config: Option<PathBuf>,
+
+    /// The news outlet that the topics are on, `bbc` (BBC Chinese) or `voa` (VOA Chinese)
+    #[clap(long, default_value = "bbc")]
+    pub source: Outlet,
+
+    /// Optional path to a selector profile, describing where to find article links on a page
+    /// (defaults to the source's built-in profile)
+    #[clap(long, short)]
+    pub selectors: Option<PathBuf>,
+
+    /// Treat the simplified and traditional Chinese versions of an article as the same article,
+    /// keeping whichever is found first
+    #[clap(long)]
+    pub collapse_scripts: bool,
+
+    /// Optional path to a SQLite store of the articles found by earlier runs, to scrape
+    /// incrementally
+    ///
+    /// Articles that aren't in the store yet are added to it, with the time they were first seen
+    /// and the topic they were found on. Each topic's pages are scraped in order, stopping at the
+    /// first page without any new articles
+    #[clap(long)]
+    pub seen: Option<PathBuf>,
+
+    /// Which articles to write to the output when using `--seen`
+    #[clap(long, value_enum, default_value_t = Export::New, requires = "seen")]
+    pub export: Export,
+
+    /// Only keep articles published on or after this day (`YYYY-MM-DD`)
+    ///
+    /// Topic pages don't say when each article was published, so whenever `--since`, `--until`
+    /// or `--per-month` is used, every article found is fetched to read its date, which is then
+    /// written after its URL. Articles without a date on the page are left out
+    #[clap(long)]
+    pub since: Option<NaiveDate>,
+
+    /// Only keep articles published on or before this day (`YYYY-MM-DD`)
+    #[clap(long)]
+    pub until: Option<NaiveDate>,
+
+    /// Optional limit for the number of articles to keep from each calendar month, picked evenly
+    /// from across the month
+    #[clap(long)]
+    pub per_month: Option<usize>,
 }
 
-fn main() -> Result<()> {
-    let Args { topics, output } = Args::parse();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Export {
+    /// Only the articles found for the first time by this run
+    New,
+    /// Every article in the store, in the order they were first seen
+    All,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    let Args {
+        topics,
+        output,
+        concurrency,
+        retries,
+        max_pages,
+        config,
+        source,
+        selectors,
+        collapse_scripts,
+        seen,
+        export,
+        since,
+        until,
+        per_month,
+    } = Args::parse();
+
+    let config = match config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    config.apply()?;
+
+    let profile = match selectors {
+        Some(path) => Some(SelectorProfile::from_file(path)?),
+        None => None,
+    };
 
-    let mut output = BufWriter::new(File::create(output)?);
+    let topics = load_topics(&topics, source)?;
 
-    let topics = load_urls(&topics)?;
+    let store = match seen {
+        Some(path) => Some(SeenStore::open(&path, collapse_scripts)?),
+        None => None,
+    };
+    let known = match &store {
+        Some(store) => Some(store.ids().await?),
+        None => None,
+    };
 
-    let urls: Vec<_> = topics
+    let mut out = BufWriter::new(File::create(output)?);
+
+    let scraper = Scraper {
+        source: source.source(profile),
+        retries,
+        max_pages,
+        permits: Semaphore::new(concurrency.max(1)),
+        progress: progress_bar(&topics, max_pages, known.is_some()),
+        known,
+        collapse_scripts,
+    };
+
+    // `join_all` keeps the results in the same order as the topics, so the output is stable
+    let results = join_all(topics.iter().map(|topic| scraper.scrape_topic(topic))).await;
+
+    scraper.progress.finish();
+
+    let mut urls = Vec::new();
+    let mut topic_of = HashMap::new();
+    let mut failed = Vec::new();
+    let mut num_pages = 0;
+
+    for (topic, result) in topics.iter().zip(results) {
+        for article in &result.articles {
+            topic_of.entry(article.clone()).or_insert(topic);
+        }
+
+        urls.extend(result.articles);
+        failed.extend(result.failed);
+        num_pages += result.pages;
+    }
+
+    let urls: Vec<(String, Option<&Topic>)> = dedup_urls(urls, collapse_scripts)
         .into_iter()
-        .map(|(url, num_pages)| scrape_topic(&url, num_pages))
-        .flatten_ok()
-        .collect::<Result<_>>()?;
+        .map(|url| {
+            let topic = topic_of[&url];
+            (url.into(), Some(topic))
+        })
+        .collect();
 
-    let urls = dedup_urls(urls);
+    let filter = DateFilter {
+        since,
+        until,
+        per_month,
+    };
 
-    for url in urls {
-        writeln!(output, "{url}")?;
+    // the articles found by this run are filtered before they're added to the store, so the ones
+    // left out can still be picked up by a later run with a different range
+    let mut total = urls.len();
+    let mut published = HashMap::new();
+    let found = scraper.filter(urls, &filter, &mut published).await;
+    let mut undated = found.undated;
+
+    let entries = match &store {
+        None => found.kept,
+        Some(store) => {
+            let articles = found
+                .kept
+                .iter()
+                .map(|(url, topic)| {
+                    let url = Url::parse(url)?;
+                    let topic = topic.map(|topic| topic.url.clone()).unwrap_or_default();
+                    Ok((url, topic))
+                })
+                .collect::<Result<_>>()?;
+            let new = store.add(articles).await?;
+            eprintln!("found {} new articles", new.len());
+
+            match export {
+                Export::New => {
+                    let new: HashSet<String> = new.into_iter().map(String::from).collect();
+                    found
+                        .kept
+                        .into_iter()
+                        .filter(|(url, _)| new.contains(url))
+                        .collect()
+                }
+                // articles found by earlier runs are labelled with their topic's current name
+                // and category, if it's still in the topics file. They're filtered again, as an
+                // earlier run might have used a different range
+                Export::All => {
+                    let articles: Vec<_> = store
+                        .all()
+                        .await?
+                        .into_iter()
+                        .map(|article| {
+                            let topic = topics.iter().find(|topic| topic.url == article.topic);
+                            (article.url, topic)
+                        })
+                        .collect();
+
+                    total += articles
+                        .iter()
+                        .filter(|(url, _)| !published.contains_key(url))
+                        .count();
+
+                    let all = scraper.filter(articles, &filter, &mut published).await;
+                    undated.extend(all.undated);
+                    all.kept
+                }
+            }
+        }
+    };
+
+    let entries: Vec<_> = entries
+        .into_iter()
+        .map(|(url, topic)| {
+            let (name, category) = topic.map_or((None, None), |topic| {
+                (topic.name.clone(), topic.category.clone())
+            });
+            let published = published.get(&url).cloned().flatten();
+            UrlEntry::new(url, published).with_topic(name, category)
+        })
+        .collect();
+    output::write_url_list(&mut out, &entries)?;
+
+    if !undated.is_empty() {
+        eprintln!(
+            "{} of {total} articles had no publication date, so were left out:",
+            undated.len()
+        );
+
+        for (url, _) in undated {
+            eprintln!("  {url}");
+        }
+    }
+
+    if !failed.is_empty() {
+        eprintln!("{} of {num_pages} pages failed:", failed.len());
+
+        for (page, e) in failed {
+            eprintln!("  {page}: {e}");
+        }
     }
 
     Ok(())
 }
 
-fn dedup_urls(urls: Vec<String>) -> Vec<String> {
+/// A topic to scrape, and the number of pages to scrape from it (if known)
+#[derive(Default)]
+struct Topic {
+    url: String,
+    pages: Option<usize>,
+    /// The topic's name and category, which are written with each of its articles
+    name: Option<String>,
+    category: Option<String>,
+    /// The source to scrape the topic with, if it has its own selectors
+    source: Option<Box<dyn NewsSource>>,
+}
+
+/// A topic in a structured (JSON5) topics file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TopicSpec {
+    url: String,
+    name: Option<String>,
+    /// e.g. `politics`, `business` or `tech`
+    category: Option<String>,
+    pages: Option<NonZeroUsize>,
+    /// A selector profile to scrape the topic with instead of the `--source` outlet's, relative to
+    /// the topics file
+    selectors: Option<PathBuf>,
+}
+
+/// Everything found while scraping a single topic
+#[derive(Default)]
+struct TopicResult {
+    articles: Vec<Url>,
+    failed: Vec<(String, Report)>,
+    /// The number of pages we tried to fetch
+    pages: usize,
+}
+
+struct Scraper {
+    source: Box<dyn NewsSource>,
+    retries: u32,
+    max_pages: Option<usize>,
+    /// Limits the number of pages being fetched at once, across all topics
+    permits: Semaphore,
+    progress: ProgressBar,
+    /// The IDs of the articles found by earlier runs, when scraping incrementally
+    known: Option<HashSet<String>>,
+    collapse_scripts: bool,
+}
+
+impl Scraper {
+    async fn scrape_topic(&self, topic: &Topic) -> TopicResult {
+        let mut result = TopicResult::default();
+
+        // when scraping incrementally, pages are scraped one at a time so we can stop at the
+        // first page without new articles
+        let incremental = self.known.is_some();
+
+        if let (Some(num_pages), false) = (topic.pages, incremental) {
+            self.scrape_pages(topic, 1..=self.cap(num_pages), &mut result)
+                .await;
+            return result;
+        }
+
+        // read the first page to find out how many pages there are
+        let url = self.source(topic).page_url(&topic.url, 1);
+        result.pages += 1;
+
+        let page = match self.load_page(topic, &url).await {
+            Ok(page) => page,
+            Err(e) => {
+                result.failed.push((url, e));
+                return result;
+            }
+        };
+
+        let num_pages = match self.articles(topic, &url, &page) {
+            Ok(articles) => {
+                let any_new = articles.iter().any(|article| !self.is_known(article));
+                result.articles.extend(articles);
+
+                if !any_new {
+                    return result;
+                }
+
+                topic
+                    .pages
+                    .or_else(|| self.source(topic).page_count(&page).unwrap_or_default())
+            }
+            Err(e) => {
+                result.failed.push((url, e));
+                return result;
+            }
+        };
+
+        match num_pages {
+            Some(num_pages) if !incremental => {
+                let num_pages = self.cap(num_pages);
+                self.progress.inc_length(num_pages.saturating_sub(1) as u64);
+                self.scrape_pages(topic, 2..=num_pages, &mut result).await;
+            }
+            num_pages => self.follow_pages(topic, num_pages, &mut result).await,
+        }
+
+        result
+    }
+
+    /// Scrape a known range of pages from a topic, all at once
+    async fn scrape_pages(
+        &self,
+        topic: &Topic,
+        pages: impl Iterator<Item = usize>,
+        result: &mut TopicResult,
+    ) {
+        let pages = pages.map(|i| async move {
+            let url = self.source(topic).page_url(&topic.url, i);
+            let articles = self.scrape_page(topic, &url).await;
+            (url, articles)
+        });
+
+        for (url, articles) in join_all(pages).await {
+            result.pages += 1;
+
+            match articles {
+                Ok(articles) => result.articles.extend(articles),
+                Err(e) => result.failed.push((url, e)),
+            }
+        }
+    }
+
+    /// Scrape pages from a topic one at a time, until a page has no articles we haven't already
+    /// seen, in this run or an earlier one (or we hit the page limit, or the last page)
+    async fn follow_pages(
+        &self,
+        topic: &Topic,
+        num_pages: Option<usize>,
+        result: &mut TopicResult,
+    ) {
+        let mut seen: HashSet<_> = result.articles.iter().cloned().collect();
+
+        for i in 2.. {
+            if self.max_pages.is_some_and(|max| i > max) || num_pages.is_some_and(|num| i > num) {
+                break;
+            }
+
+            let url = self.source(topic).page_url(&topic.url, i);
+            self.progress.inc_length(1);
+            result.pages += 1;
+
+            let page = match self.load_page(topic, &url).await {
+                Ok(page) => page,
+                Err(e) => {
+                    result.failed.push((url, e));
+                    break;
+                }
+            };
+
+            let articles = match self.source(topic).article_links(&url, &page) {
+                Ok(articles) => articles,
+                Err(e) => {
+                    result.failed.push((url, e));
+                    break;
+                }
+            };
+
+            let new: Vec<_> = articles
+                .into_iter()
+                .filter(|article| seen.insert(article.clone()) && !self.is_known(article))
+                .collect();
+
+            if new.is_empty() {
+                break;
+            }
+
+            result.articles.extend(new);
+        }
+    }
+
+    /// Fetch a single topic page and return the article URLs on it
+    async fn scrape_page(&self, topic: &Topic, url: &str) -> Result<Vec<Url>> {
+        let page = self.load_page(topic, url).await?;
+        self.articles(topic, url, &page)
+    }
+
+    /// Fetch a single page
+    ///
+    /// Pages that fail to load are retried, waiting twice as long each time
+    async fn load_page(&self, topic: &Topic, url: &str) -> Result<String> {
+        let _permit = self.permits.acquire().await?;
+
+        let mut delay = Duration::from_secs(1);
+        let mut attempt = 0;
+
+        let result = loop {
+            match self.source(topic).load_html(url).await {
+                Ok(page) => break Ok(page),
+                Err(e) if attempt >= self.retries => break Err(e),
+                Err(e) => {
+                    tracing::warn!("failed to load {url}, retrying: {e}");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        };
+
+        self.progress.inc(1);
+        result
+    }
+
+    /// Apply the date filter to articles, reading each one's publication date first if the
+    /// filter needs it
+    ///
+    /// The dates are added to `published`, by URL, and articles already in it aren't read again
+    async fn filter<'a>(
+        &self,
+        articles: Vec<(String, Option<&'a Topic>)>,
+        filter: &DateFilter,
+        published: &mut HashMap<String, Option<String>>,
+    ) -> Filtered<(String, Option<&'a Topic>)> {
+        if filter.is_active() {
+            let unread: Vec<_> = articles
+                .iter()
+                .filter(|(url, _)| !published.contains_key(url))
+                .collect();
+
+            let progress = dates_progress_bar(unread.len());
+            let dates = unread
+                .iter()
+                .map(|(url, topic)| self.published(url, *topic, &progress));
+            let dates = join_all(dates).await;
+            progress.finish();
+
+            for ((url, _), date) in unread.into_iter().zip(dates) {
+                published.insert(url.clone(), date);
+            }
+        }
+
+        filter.apply(articles, |(url, _)| {
+            published.get(url)?.as_deref().and_then(dates::parse)
+        })
+    }
+
+    /// Fetch an article to read when it was published, as the page gives it
+    ///
+    /// Returns `None` if the article fails to load, or doesn't say
+    async fn published(
+        &self,
+        url: &str,
+        topic: Option<&Topic>,
+        progress: &ProgressBar,
+    ) -> Option<String> {
+        let source = topic.map_or(self.source.as_ref(), |topic| self.source(topic));
+
+        let article = {
+            let _permit = self.permits.acquire().await.ok()?;
+            source.article(url).await
+        };
+        progress.inc(1);
+
+        match article {
+            Ok(article) => article.metadata.published,
+            Err(e) => {
+                tracing::warn!("failed to load {url}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Return the article URLs on a page, treating a page without any as an error
+    fn articles(&self, topic: &Topic, url: &str, page: &str) -> Result<Vec<Url>> {
+        let articles = self.source(topic).article_links(url, page)?;
+        if articles.is_empty() {
+            tracing::warn!("no article links found on {url}");
+            bail!("no article links found, the selectors may be out of date");
+        }
+
+        Ok(articles)
+    }
+
+    /// The source to scrape a topic with, which has the topic's own selectors if it has any
+    fn source<'t>(&'t self, topic: &'t Topic) -> &'t dyn NewsSource {
+        topic.source.as_deref().unwrap_or(self.source.as_ref())
+    }
+
+    /// Whether an article was found by an earlier run
+    fn is_known(&self, article: &Url) -> bool {
+        self.known
+            .as_ref()
+            .is_some_and(|known| known.contains(&urls::article_id(article, self.collapse_scripts)))
+    }
+
+    fn cap(&self, num_pages: usize) -> usize {
+        self.max_pages.map_or(num_pages, |max| num_pages.min(max))
+    }
+}
+
+/// Make a progress bar for the pages we know about up front (one for each topic without a known
+/// number of pages, with more added as they are discovered)
+///
+/// When scraping incrementally, every topic starts with one page, as we don't know how many
+/// pages it will take to reach articles we already have
+fn progress_bar(topics: &[Topic], max_pages: Option<usize>, incremental: bool) -> ProgressBar {
+    let sty = ProgressStyle::with_template("{msg:26}: {bar} {pos:>7}/{len:7} ").unwrap();
+
+    let count: usize = topics
+        .iter()
+        .map(|topic| match topic.pages {
+            Some(pages) if !incremental => max_pages.map_or(pages, |max| pages.min(max)),
+            _ => 1,
+        })
+        .sum();
+
+    ProgressBar::new(count as u64)
+        .with_style(sty)
+        .with_message("Scraping topic pages")
+}
+
+/// Make a progress bar for fetching articles to read their dates
+fn dates_progress_bar(count: usize) -> ProgressBar {
+    let sty = ProgressStyle::with_template("{msg:26}: {bar} {pos:>7}/{len:7} ").unwrap();
+
+    ProgressBar::new(count as u64)
+        .with_style(sty)
+        .with_message("Reading article dates")
+}
+
+/// Remove repeated articles, keeping the first URL found for each
+fn dedup_urls(urls: Vec<Url>, collapse_scripts: bool) -> Vec<Url> {
     let mut result = Vec::with_capacity(urls.len());
     let mut seen = HashSet::new();
 
     for url in urls {
-        if seen.contains(&url) {
-            continue;
-        } else {
-            seen.insert(url.clone());
+        if seen.insert(urls::article_id(&url, collapse_scripts)) {
             result.push(url);
         }
     }
@@ -57,49 +635,166 @@ fn dedup_urls(urls: Vec<String>) -> Vec<String> {
     result
 }
 
-fn load_urls(path: &Path) -> Result<Vec<(String, usize)>> {
+/// Read the topics file
+///
+/// A `.json5` or `.json` file is a list of [`TopicSpec`]s. Any other file has a topic on each line,
+/// `<url> [num_pages]`, where blank lines, and lines starting with `#`, are ignored
+fn load_topics(path: &Path, outlet: Outlet) -> Result<Vec<Topic>> {
     let text = std::fs::read_to_string(path)?;
-    let vec = text
-        .lines()
-        .map(|s| {
-            let mut words = s.split(' ');
 
-            let url = words.next().unwrap();
-            let num = words.next().unwrap().parse().unwrap();
+    let is_json5 = path
+        .extension()
+        .is_some_and(|extension| extension == "json5" || extension == "json");
+    if is_json5 {
+        let specs: Vec<TopicSpec> =
+            json5::from_str(&text).map_err(|e| eyre!("{}: {e}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
 
-            (url.to_string(), num)
-        })
-        .collect();
+        return specs
+            .into_iter()
+            .map(|spec| topic_from_spec(spec, dir, outlet))
+            .collect();
+    }
 
-    Ok(vec)
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| parse_topic(line).map_err(|e| eyre!("{}:{}: {e}", path.display(), i + 1)))
+        .collect()
 }
 
-fn scrape_topic(topic_url: &str, num_pages: usize) -> Result<Vec<String>> {
-    (1..=num_pages)
-        .map(|i| {
-            let url = format!("{topic_url}?page={i}");
-            let page = reqwest::blocking::get(url)?.text()?;
-            articles(&page)
-        })
-        .flatten_ok()
-        .collect()
+fn topic_from_spec(spec: TopicSpec, dir: &Path, outlet: Outlet) -> Result<Topic> {
+    let source = match spec.selectors {
+        Some(path) => {
+            let path = dir.join(path);
+            let profile =
+                SelectorProfile::from_file(&path).map_err(|e| eyre!("{}: {e}", path.display()))?;
+            Some(outlet.source(Some(profile)))
+        }
+        None => None,
+    };
+
+    Ok(Topic {
+        url: spec.url,
+        pages: spec.pages.map(NonZeroUsize::get),
+        name: spec.name,
+        category: spec.category,
+        source,
+    })
 }
 
-/// Return all the article URLs on a page
-fn articles(page: &str) -> Result<Vec<String>> {
-    let html = tl::parse(page, ParserOptions::default())?;
+fn parse_topic(line: &str) -> Result<Topic> {
+    let mut words = line.split_whitespace();
 
-    let articles = html
-        .query_selector("a.bbc-uk8dsi")
-        .ok_or_else(|| eyre!("no matching nodes"))?;
+    let Some(url) = words.next() else {
+        bail!("expected `<url> [num_pages]`");
+    };
 
-    let mut vec = Vec::with_capacity(20);
+    let pages = match words.next() {
+        Some(num) => match num.parse() {
+            Ok(0) | Err(_) => bail!("expected a positive number of pages, found `{num}`"),
+            Ok(num) => Some(num),
+        },
+        None => None,
+    };
 
-    for article in articles {
-        let tag = article.get(html.parser()).unwrap().as_tag().unwrap();
-        let link_bytes = tag.attributes().get("href").unwrap().unwrap();
-        vec.push(link_bytes.as_utf8_str().to_string());
+    if let Some(extra) = words.next() {
+        bail!("unexpected `{extra}` after the number of pages");
     }
 
-    Ok(vec)
+    Ok(Topic {
+        url: url.to_string(),
+        pages,
+        ..Topic::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_topic_lines() {
+        let topic = parse_topic("https://www.bbc.com/zhongwen/simp/topics/c1 5").unwrap();
+        assert_eq!(topic.url, "https://www.bbc.com/zhongwen/simp/topics/c1");
+        assert_eq!(topic.pages, Some(5));
+
+        let topic = parse_topic("https://www.bbc.com/zhongwen/simp/topics/c1").unwrap();
+        assert_eq!(topic.pages, None);
+
+        assert!(parse_topic("https://www.bbc.com/zhongwen/simp/topics/c1 five").is_err());
+        assert!(parse_topic("https://www.bbc.com/zhongwen/simp/topics/c1 0").is_err());
+        assert!(parse_topic("https://www.bbc.com/zhongwen/simp/topics/c1 5 6").is_err());
+    }
+
+    #[test]
+    fn loads_structured_topics() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("topics.json5");
+        let topics = r#"[
+          { name: "economy", category: "business", url: "https://www.bbc.com/zhongwen/simp/topics/c1", pages: 2 },
+          // a topic with its own selectors, in a file next to the topics file
+          { url: "https://www.bbc.com/zhongwen/simp/topics/c2", selectors: "c2.json5" },
+        ]"#;
+        std::fs::write(&path, topics).unwrap();
+        std::fs::write(dir.path().join("c2.json5"), r#"{ links: ["main a"] }"#).unwrap();
+
+        let topics = load_topics(&path, Outlet::BbcChinese).unwrap();
+
+        assert_eq!(topics[0].name.as_deref(), Some("economy"));
+        assert_eq!(topics[0].category.as_deref(), Some("business"));
+        assert_eq!(topics[0].pages, Some(2));
+        assert!(topics[0].source.is_none());
+
+        assert_eq!(topics[1].name, None);
+        assert_eq!(topics[1].pages, None);
+        let source = topics[1].source.as_ref().unwrap();
+        assert_eq!(source.profile().links, ["main a"]);
+
+        std::fs::write(&path, r#"[{ url: "https://www.bbc.com/", pages: 0 }]"#).unwrap();
+        assert!(load_topics(&path, Outlet::BbcChinese).is_err());
+
+        std::fs::write(
+            &path,
+            r#"[{ url: "https://www.bbc.com/", section: "news" }]"#,
+        )
+        .unwrap();
+        assert!(load_topics(&path, Outlet::BbcChinese).is_err());
+    }
+
+    #[test]
+    fn dedups_articles() {
+        let urls: Vec<_> = [
+            "https://www.bbc.com/zhongwen/simp/world-1",
+            "https://www.bbc.com/zhongwen/trad/world-1",
+            "http://bbc.com/zhongwen/simp/world-1",
+            "https://www.bbc.com/zhongwen/simp/world-2",
+        ]
+        .into_iter()
+        .map(|url| Url::parse(url).unwrap())
+        .collect();
+
+        let dedup = |collapse_scripts| -> Vec<_> {
+            dedup_urls(urls.clone(), collapse_scripts)
+                .into_iter()
+                .map(String::from)
+                .collect()
+        };
+
+        assert_eq!(
+            dedup(false),
+            [
+                "https://www.bbc.com/zhongwen/simp/world-1",
+                "https://www.bbc.com/zhongwen/trad/world-1",
+                "https://www.bbc.com/zhongwen/simp/world-2",
+            ]
+        );
+        assert_eq!(
+            dedup(true),
+            [
+                "https://www.bbc.com/zhongwen/simp/world-1",
+                "https://www.bbc.com/zhongwen/simp/world-2",
+            ]
+        );
+    }
 }
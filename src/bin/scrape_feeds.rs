@@ -0,0 +1,167 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::path::Path;
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use clap::Parser;
+use color_eyre::{Report, Result};
+use dissertation::{
+    config::Config,
+    feeds,
+    html::SelectorProfile,
+    input::UrlEntry,
+    output,
+    sources::{Outlet, Sources},
+    urls,
+};
+use futures::future::join_all;
+use reqwest::Url;
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// The path to the file containing the sitemap and feed URLs
+    ///
+    /// Each line is the URL of an XML sitemap, sitemap index, Google News sitemap, RSS feed or
+    /// Atom feed. Blank lines, and lines starting with `#`, are ignored
+    #[clap(long, short = 'i')]
+    pub sources: PathBuf,
+
+    /// The path to the output URL file
+    #[clap(long, short)]
+    pub output: PathBuf,
+
+    /// Optional path to a config file, containing settings such as rate limits
+    #[clap(long, short)]
+    pub config: Option<PathBuf>,
+
+    /// The news outlet for sitemaps and feeds that aren't on a known outlet's site, `bbc` (BBC
+    /// Chinese) or `voa` (VOA Chinese)
+    ///
+    /// Each sitemap or feed is fetched under its outlet's rate limit, and that outlet's link
+    /// patterns decide which of the URLs it lists are articles
+    #[clap(long, default_value = "bbc")]
+    pub source: Outlet,
+
+    /// Optional path to a selector profile for the `--source` outlet, whose link patterns
+    /// decide which URLs are articles (defaults to the outlet's built-in profile)
+    #[clap(long, short)]
+    pub selectors: Option<PathBuf>,
+
+    /// Treat the simplified and traditional Chinese versions of an article as the same article,
+    /// keeping whichever is found first
+    #[clap(long)]
+    pub collapse_scripts: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    let Args {
+        sources,
+        output,
+        config,
+        source,
+        selectors,
+        collapse_scripts,
+    } = Args::parse();
+
+    let config = match config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    config.apply()?;
+
+    let profile = match selectors {
+        Some(path) => Some(SelectorProfile::from_file(path)?),
+        None => None,
+    };
+    let outlets = Sources::new(source, profile);
+
+    let sources = load_sources(&sources)?;
+
+    // `join_all` keeps the results in the same order as the sources, so the output is stable
+    let results = join_all(
+        sources
+            .iter()
+            .map(|source| feeds::discover(source, outlets.for_url(source).service())),
+    )
+    .await;
+
+    let mut entries = Vec::new();
+    let mut failed: Vec<(&String, Report)> = Vec::new();
+
+    for (source, result) in sources.iter().zip(results) {
+        let found = match result {
+            Ok(found) => found,
+            Err(e) => {
+                failed.push((source, e));
+                continue;
+            }
+        };
+
+        let base = Url::parse(source)?;
+        let outlet = outlets.for_url(source);
+
+        for UrlEntry { url, published, .. } in found {
+            let Some(url) = urls::canonicalise(&base, &url) else {
+                continue;
+            };
+
+            if outlet.is_article(&url) {
+                entries.push((url, published));
+            }
+        }
+    }
+
+    let entries = dedup_entries(entries, collapse_scripts);
+    tracing::info!("found {} articles", entries.len());
+
+    let out = BufWriter::new(File::create(output)?);
+    output::write_url_list(out, &entries)?;
+
+    if !failed.is_empty() {
+        eprintln!("{} of {} sources failed:", failed.len(), sources.len());
+
+        for (source, e) in failed {
+            eprintln!("  {source}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove repeated articles, keeping the first URL and the first publication date found for each
+fn dedup_entries(entries: Vec<(Url, Option<String>)>, collapse_scripts: bool) -> Vec<UrlEntry> {
+    let mut result: Vec<UrlEntry> = Vec::with_capacity(entries.len());
+    let mut seen = HashMap::new();
+
+    for (url, published) in entries {
+        match seen.entry(urls::article_id(&url, collapse_scripts)) {
+            Entry::Vacant(slot) => {
+                slot.insert(result.len());
+                result.push(UrlEntry::new(url.into(), published));
+            }
+            Entry::Occupied(slot) => {
+                let existing = &mut result[*slot.get()];
+                existing.published = existing.published.take().or(published);
+            }
+        }
+    }
+
+    result
+}
+
+/// Read the sources file, where each line is a sitemap or feed URL
+fn load_sources(path: &Path) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
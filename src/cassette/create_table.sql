@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS interactions (
+  key TEXT PRIMARY KEY,
+  status INTEGER NOT NULL,
+  headers TEXT NOT NULL,
+  body BLOB NOT NULL
+);
@@ -0,0 +1,296 @@
+use std::{fmt, path::Path, sync::OnceLock};
+
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Request, StatusCode,
+};
+use rusqlite::{named_params, OpenFlags, OptionalExtension};
+
+use crate::{audit::redact_url, http_client::HttpResponse};
+
+static CASSETTE: OnceLock<Cassette> = OnceLock::new();
+
+/// URL path segments that hold a credential, by the segment before them, and the name of the
+/// environment variable the credential comes from
+///
+/// The credential is replaced with the name when building a cassette key, so a cassette recorded
+/// with one set of credentials can be replayed with another (or with none at all). Credentials in
+/// query parameters are removed by [`redact_url`], and headers aren't part of the key
+const CREDENTIAL_SEGMENTS: &[(&str, &str)] = &[("projects", "GCLOUD_PROJECT_ID")];
+
+/// The error returned when replaying a request that was never recorded
+///
+/// This means the replayed run has diverged from the recorded one, so unlike a page that fails to
+/// load, it shouldn't be treated as a gap in the data. It is returned inside the [`Report`], so it
+/// can be told apart from other errors with `report.is::<CassetteMiss>()`
+///
+/// [`Report`]: color_eyre::Report
+#[derive(Debug)]
+pub struct CassetteMiss {
+    /// The key of the request, as made by [`Cassette::key`]
+    pub key: String,
+}
+
+impl fmt::Display for CassetteMiss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request not found in cassette:\n{}", self.key)
+    }
+}
+
+impl std::error::Error for CassetteMiss {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CassetteMode {
+    /// Send requests as normal, and save every response to the cassette
+    Record,
+    /// Never touch the network, and serve every response from the cassette
+    Replay,
+}
+
+/// A recording of every HTTP interaction made during a run
+///
+/// A cassette recorded during one run can be replayed during a later one, which then produces
+/// exactly the same output without sending any requests, even if the APIs have since changed.
+///
+/// Note that Google Trends scores are only requested when they aren't already in the trends
+/// database, so a cassette should be recorded with an empty database (or shipped along with the
+/// database it was recorded with)
+pub struct Cassette {
+    pool: Pool<SqliteConnectionManager>,
+    mode: CassetteMode,
+}
+
+impl Cassette {
+    /// Start recording to, or replaying from, the cassette at the given path
+    pub fn enable(path: &Path, mode: CassetteMode) -> Result<&'static Self> {
+        let manager = match mode {
+            CassetteMode::Record => SqliteConnectionManager::file(path),
+            CassetteMode::Replay => {
+                if !path.exists() {
+                    bail!("no cassette found at {}", path.display());
+                }
+
+                SqliteConnectionManager::file(path).with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY)
+            }
+        };
+
+        let pool = Pool::new(manager)?;
+
+        if mode == CassetteMode::Record {
+            let sql = include_str!("./create_table.sql");
+            pool.get()?.execute(sql, [])?;
+        }
+
+        CASSETTE
+            .set(Self { pool, mode })
+            .map_err(|_| eyre!("a cassette has already been enabled"))?;
+
+        Ok(CASSETTE.get().unwrap())
+    }
+
+    /// Get a handle to the global cassette, if one has been enabled
+    pub fn get() -> Option<&'static Self> {
+        CASSETTE.get()
+    }
+
+    /// Whether responses are currently being served from a cassette rather than the network
+    pub fn is_replaying() -> bool {
+        Self::get().is_some_and(|cassette| cassette.mode == CassetteMode::Replay)
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// The key that identifies a request in the cassette
+    ///
+    /// This is made from the method, URL and body, with any credentials removed
+    pub(crate) fn key(request: &Request) -> String {
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+
+        let mut url = request.url().clone();
+        let path = redact_path(url.path());
+        url.set_path(&path);
+
+        format!("{} {}\n{body}", request.method(), redact_url(&url))
+    }
+
+    /// Save a response so it can be replayed later
+    ///
+    /// If the same request is made more than once, the latest response wins
+    pub(crate) async fn record(&self, key: String, response: &HttpResponse) -> Result<()> {
+        let conn = self.pool.get()?;
+        let status = response.status.as_u16();
+        let headers = headers_to_json(&response.headers);
+        let body = response.body.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let sql = "INSERT OR REPLACE INTO interactions (key, status, headers, body) \
+                VALUES (:key, :status, :headers, :body)";
+            let mut statement = conn.prepare_cached(sql)?;
+            statement.execute(named_params! {
+                ":key": key,
+                ":status": status,
+                ":headers": headers,
+                ":body": body,
+            })?;
+
+            Ok(())
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Load the recorded response for a request
+    ///
+    /// A request that was never recorded is a [`CassetteMiss`] error
+    pub(crate) async fn replay(&self, key: String) -> Result<HttpResponse> {
+        let conn = self.pool.get()?;
+
+        let (key, row) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let sql = "SELECT status, headers, body FROM interactions WHERE key = (?1)";
+            let mut statement = conn.prepare_cached(sql)?;
+            let row = statement
+                .query_row([&key], |row| {
+                    Ok((
+                        row.get::<_, u16>("status")?,
+                        row.get::<_, String>("headers")?,
+                        row.get::<_, Vec<u8>>("body")?,
+                    ))
+                })
+                .optional()?;
+
+            Ok((key, row))
+        })
+        .await??;
+
+        let Some((status, headers, body)) = row else {
+            return Err(CassetteMiss { key }.into());
+        };
+
+        Ok(HttpResponse {
+            status: StatusCode::from_u16(status)?,
+            headers: headers_from_json(&headers)?,
+            body,
+        })
+    }
+}
+
+/// Replace the credentials in [`CREDENTIAL_SEGMENTS`] in a URL path with their names
+///
+/// Only the part of a segment before any `:` is replaced, as Google APIs add a method to the end
+/// (e.g. `projects/my-project:translateText`)
+fn redact_path(path: &str) -> String {
+    let mut previous = None;
+
+    let segments: Vec<_> = path
+        .split('/')
+        .map(|segment| {
+            let name = CREDENTIAL_SEGMENTS
+                .iter()
+                .find(|(before, _)| previous == Some(*before))
+                .map(|(_, name)| *name);
+            previous = Some(segment);
+
+            match (name, segment.split_once(':')) {
+                (Some(name), Some((_, method))) => format!("{name}:{method}"),
+                (Some(name), None) if !segment.is_empty() => name.to_string(),
+                _ => segment.to_string(),
+            }
+        })
+        .collect();
+
+    segments.join("/")
+}
+
+pub(crate) fn headers_to_json(headers: &HeaderMap) -> String {
+    let pairs: Vec<(&str, String)> = headers
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.as_str(), value)
+        })
+        .collect();
+
+    serde_json::to_string(&pairs).unwrap()
+}
+
+pub(crate) fn headers_from_json(json: &str) -> Result<HeaderMap> {
+    let pairs: Vec<(String, String)> = serde_json::from_str(json)?;
+    let mut headers = HeaderMap::with_capacity(pairs.len());
+
+    for (name, value) in pairs {
+        headers.append(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Method, Url};
+
+    use super::*;
+
+    #[test]
+    fn key_ignores_api_key() {
+        let url = "https://example.com/graph?terms=foo&key=secret";
+        let request = Request::new(Method::GET, Url::parse(url).unwrap());
+
+        assert_eq!(
+            Cassette::key(&request),
+            "GET https://example.com/graph?terms=foo&key=REDACTED\n"
+        );
+    }
+
+    #[test]
+    fn key_ignores_project_id() {
+        let url = "https://example.com/v3beta1/projects/my-project:translateText";
+        let request = Request::new(Method::POST, Url::parse(url).unwrap());
+
+        assert_eq!(
+            Cassette::key(&request),
+            "POST https://example.com/v3beta1/projects/GCLOUD_PROJECT_ID:translateText\n"
+        );
+
+        // the same text anywhere else is left alone
+        let url = "https://example.com/my-project/projects?q=my-project";
+        let request = Request::new(Method::GET, Url::parse(url).unwrap());
+        assert_eq!(
+            Cassette::key(&request),
+            "GET https://example.com/my-project/projects?q=my-project\n"
+        );
+    }
+
+    #[test]
+    fn headers_round_trip() {
+        let headers = HeaderMap::from_iter([
+            (
+                HeaderName::from_static("content-type"),
+                HeaderValue::from_static("text/html"),
+            ),
+            (
+                HeaderName::from_static("etag"),
+                HeaderValue::from_static("\"abc\""),
+            ),
+        ]);
+
+        let json = headers_to_json(&headers);
+        assert_eq!(headers_from_json(&json).unwrap(), headers);
+    }
+}
@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::Deserialize;
+
+use crate::{
+    http_client::{Clients, HttpSettings, DEFAULT_USER_AGENT},
+    rate_limiter::{RateLimiters, RateLimits},
+};
+
+/// Settings that can be changed without recompiling, loaded from a JSON5 file
+///
+/// Every field is optional, and anything left out keeps its default value
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rate_limits: RateLimits,
+    /// The user agent sent to the sites we scrape, defaults to one naming this crawler
+    pub user_agent: Option<String>,
+    /// Timeouts, connection pooling, HTTP/2 and proxy settings for each HTTP client
+    pub http: HttpSettings,
+}
+
+impl Config {
+    /// Load the config from a given json5 file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(path)?;
+        Ok(json5::from_str(&s)?)
+    }
+
+    /// Set up the global rate limiters and http clients with these settings
+    ///
+    /// This must be called before the first request is sent
+    pub fn apply(&self) -> Result<()> {
+        RateLimiters::init(&self.rate_limits)?;
+        let user_agent = self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+        Clients::init(user_agent, &self.http)
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, NaiveDate};
+
+/// Read the day a page was published from a date as a page or feed gives it
+///
+/// This understands RFC 3339 (e.g. `2023-08-07T10:00:00+08:00`), RFC 2822 (as RSS uses), and
+/// anything starting with a plain `YYYY-MM-DD` date. Dates with a time zone give the day in that
+/// time zone, which is the day the outlet says the page was published
+pub fn parse(date: &str) -> Option<NaiveDate> {
+    let date = date.trim();
+
+    let datetime = DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(date))
+        .ok();
+
+    if let Some(datetime) = datetime {
+        return Some(datetime.date_naive());
+    }
+
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Which pages to keep, by when they were published
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateFilter {
+    /// The first day to keep pages from
+    pub since: Option<NaiveDate>,
+    /// The last day to keep pages from
+    pub until: Option<NaiveDate>,
+    /// The most pages to keep from each calendar month, picked evenly from across the month
+    pub per_month: Option<usize>,
+}
+
+/// The result of [`DateFilter::apply`]
+#[derive(Debug)]
+pub struct Filtered<T> {
+    pub kept: Vec<T>,
+    /// The items that were left out because their publication date isn't known
+    pub undated: Vec<T>,
+}
+
+impl DateFilter {
+    /// Whether the filter leaves anything out, so the publication date of every page is needed
+    pub fn is_active(&self) -> bool {
+        self.since.is_some() || self.until.is_some() || self.per_month.is_some()
+    }
+
+    /// Whether a date is between `since` and `until` (inclusive)
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.since.map_or(true, |since| date >= since)
+            && self.until.map_or(true, |until| date <= until)
+    }
+
+    /// Keep the items published within the range, and then at most `per_month` from each month
+    ///
+    /// An item's date is given by `date`. Within a month, the items are spread out by date and
+    /// the ones in the middle of each equal share are kept, so that the sample isn't bunched up
+    /// at the start or end of the month. The kept items stay in the order they were given in
+    pub fn apply<T>(&self, items: Vec<T>, date: impl Fn(&T) -> Option<NaiveDate>) -> Filtered<T> {
+        if !self.is_active() {
+            return Filtered {
+                kept: items,
+                undated: vec![],
+            };
+        }
+
+        let mut dated = Vec::new();
+        let mut undated = Vec::new();
+
+        for item in items {
+            match date(&item) {
+                Some(date) if self.contains(date) => dated.push((date, item)),
+                Some(_) => {}
+                None => undated.push(item),
+            }
+        }
+
+        let Some(per_month) = self.per_month else {
+            let kept = dated.into_iter().map(|(_, item)| item).collect();
+            return Filtered { kept, undated };
+        };
+
+        // the positions of the items published in each month, oldest first
+        let mut months: BTreeMap<_, Vec<usize>> = BTreeMap::new();
+        for (i, (date, _)) in dated.iter().enumerate() {
+            months
+                .entry((date.year(), date.month()))
+                .or_default()
+                .push(i);
+        }
+
+        let mut keep = vec![false; dated.len()];
+        for positions in months.values_mut() {
+            positions.sort_by_key(|&i| dated[i].0);
+
+            let count = positions.len();
+            let picks = per_month.min(count);
+            for pick in 0..picks {
+                let index = (2 * pick + 1) * count / (2 * picks);
+                keep[positions[index]] = true;
+            }
+        }
+
+        let kept = dated
+            .into_iter()
+            .zip(keep)
+            .filter_map(|((_, item), keep)| keep.then_some(item))
+            .collect();
+
+        Filtered { kept, undated }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse("2023-08-07"), Some(day("2023-08-07")));
+        assert_eq!(parse("2023-08-07T10:00:00Z"), Some(day("2023-08-07")));
+
+        // the day is the one in the page's own time zone
+        assert_eq!(parse("2023-08-07T01:00:00+08:00"), Some(day("2023-08-07")));
+        assert_eq!(
+            parse("Mon, 07 Aug 2023 09:30:00 +0800"),
+            Some(day("2023-08-07"))
+        );
+        assert_eq!(parse("2023-08-07T10:00"), Some(day("2023-08-07")));
+
+        assert_eq!(parse("2023年8月7日"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn filters_by_range() {
+        let filter = DateFilter {
+            since: Some(day("2023-01-01")),
+            until: Some(day("2023-12-31")),
+            per_month: None,
+        };
+        let items = vec![
+            "2022-12-31",
+            "2023-01-01",
+            "unknown",
+            "2023-12-31",
+            "2024-01-01",
+        ];
+
+        let filtered = filter.apply(items, |item| parse(item));
+        assert_eq!(filtered.kept, ["2023-01-01", "2023-12-31"]);
+        assert_eq!(filtered.undated, ["unknown"]);
+
+        // without a filter, nothing is left out, even without a date
+        let filtered = DateFilter::default().apply(vec!["unknown"], |item| parse(item));
+        assert_eq!(filtered.kept, ["unknown"]);
+    }
+
+    #[test]
+    fn samples_evenly_across_months() {
+        let filter = DateFilter {
+            per_month: Some(2),
+            ..DateFilter::default()
+        };
+        let items = vec![
+            "2023-02-20",
+            "2023-01-01",
+            "2023-01-10",
+            "2023-01-20",
+            "2023-01-30",
+            "2023-02-10",
+            "2023-03-15",
+        ];
+
+        let filtered = filter.apply(items, |item| parse(item));
+
+        // the 2nd and 4th of January's four, both of February's, and March's only one
+        assert_eq!(
+            filtered.kept,
+            [
+                "2023-02-20",
+                "2023-01-10",
+                "2023-01-30",
+                "2023-02-10",
+                "2023-03-15"
+            ]
+        );
+    }
+}
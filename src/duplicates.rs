@@ -0,0 +1,185 @@
+use std::collections::{HashMap, HashSet};
+
+/// The number of characters in each shingle that texts are compared by
+///
+/// Chinese words are mostly one or two characters, so three characters is about a short phrase
+const SHINGLE_CHARS: usize = 3;
+
+/// The number of hash functions in each MinHash signature, split into [`BANDS`] bands
+const SIGNATURE_LEN: usize = BANDS * ROWS;
+
+/// Locality-sensitive hashing: texts whose signatures match in all the rows of any band are
+/// compared properly
+///
+/// With 32 bands of 4 rows, a pair of texts with a similarity of 0.5 is compared 87% of the time,
+/// 0.6 99% of the time, and anything above 0.7 practically always
+const BANDS: usize = 32;
+const ROWS: usize = 4;
+
+/// Group texts that are nearly the same into clusters, using MinHash
+///
+/// Each text is broken into overlapping runs of characters (ignoring punctuation and spacing),
+/// and two texts are near-duplicates if the [Jaccard similarity] of their sets of runs is at
+/// least `threshold` (0 to 1). Clusters are joined transitively, so a text in a cluster might only
+/// be similar to another text in it, rather than all of them.
+///
+/// Returns the clusters of two or more texts, as indices into `texts`, in order of their first
+/// text. `None` (a page without the text) is never a duplicate
+///
+/// [Jaccard similarity]: https://en.wikipedia.org/wiki/Jaccard_index
+pub fn clusters(texts: &[Option<&str>], threshold: f64) -> Vec<Vec<usize>> {
+    let shingles: Vec<Option<HashSet<u64>>> = texts
+        .iter()
+        .map(|text| text.map(shingles).filter(|shingles| !shingles.is_empty()))
+        .collect();
+
+    let signatures: Vec<_> = shingles
+        .iter()
+        .map(|shingles| shingles.as_ref().map(signature))
+        .collect();
+
+    // the candidate pairs, found by bucketing each band of the signatures
+    let mut candidates = HashSet::new();
+    let mut buckets: HashMap<(usize, &[u64]), Vec<usize>> = HashMap::new();
+
+    for (i, signature) in signatures.iter().enumerate() {
+        let Some(signature) = signature else {
+            continue;
+        };
+
+        for (band, rows) in signature.chunks(ROWS).enumerate() {
+            let bucket = buckets.entry((band, rows)).or_default();
+            candidates.extend(bucket.iter().map(|&j| (j, i)));
+            bucket.push(i);
+        }
+    }
+
+    let mut parents: Vec<usize> = (0..texts.len()).collect();
+
+    for (i, j) in candidates {
+        let (Some(a), Some(b)) = (&shingles[i], &shingles[j]) else {
+            continue;
+        };
+
+        if jaccard(a, b) >= threshold {
+            let (root_i, root_j) = (root(&mut parents, i), root(&mut parents, j));
+            // the earlier text is the root, so each cluster is keyed by its first text
+            parents[root_i.max(root_j)] = root_i.min(root_j);
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..texts.len() {
+        let root = root(&mut parents, i);
+        clusters.entry(root).or_default().push(i);
+    }
+
+    let mut clusters: Vec<_> = clusters
+        .into_values()
+        .filter(|cluster| cluster.len() > 1)
+        .collect();
+    clusters.sort();
+    clusters
+}
+
+/// The hashes of the runs of [`SHINGLE_CHARS`] letters and digits in a text
+///
+/// A text shorter than that is a single run
+fn shingles(text: &str) -> HashSet<u64> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    if chars.len() < SHINGLE_CHARS {
+        return chars.windows(chars.len().max(1)).map(hash).collect();
+    }
+
+    chars.windows(SHINGLE_CHARS).map(hash).collect()
+}
+
+/// The MinHash signature of a set of shingles: the smallest value of each of the hash functions
+fn signature(shingles: &HashSet<u64>) -> Vec<u64> {
+    (0..SIGNATURE_LEN as u64)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|&shingle| mix(shingle ^ seed.wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+/// Hash a shingle with 64-bit FNV-1a, which (unlike the standard library's hasher) gives the same
+/// result on every run and platform
+fn hash(chars: &[char]) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325;
+    for &c in chars {
+        for byte in (c as u32).to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+    hash
+}
+
+/// The SplitMix64 finaliser, to turn one hash into many independent-looking ones
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// The root of an element in a union-find forest, flattening the path to it along the way
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = "中国国家统计局周一公布的数据显示，中国第二季度经济增长放缓，政府宣布了一系列新的刺激措施。";
+
+    #[test]
+    fn clusters_near_duplicates() {
+        // a live page with the same description, and a follow-up that adds a word to it
+        let live = DESCRIPTION;
+        let updated = "中国国家统计局周一公布的数据显示，中国第二季度经济增长明显放缓，政府宣布了一系列新的刺激措施。";
+        let other = "美国总统拜登周二在白宫会见了来访的日本首相，双方讨论了地区安全问题。";
+
+        let texts = [
+            Some(DESCRIPTION),
+            Some(other),
+            None,
+            Some(updated),
+            Some(live),
+        ];
+        assert_eq!(clusters(&texts, 0.8), [vec![0, 3, 4]]);
+
+        // only the exact copies are left at the strictest threshold
+        assert_eq!(clusters(&texts, 1.0), [vec![0, 4]]);
+    }
+
+    #[test]
+    fn ignores_punctuation_and_spacing() {
+        let texts = [
+            Some(DESCRIPTION),
+            Some("中国国家统计局周一公布的数据显示 中国第二季度经济增长放缓 政府宣布了一系列新的刺激措施"),
+            Some("，。"),
+            Some(""),
+        ];
+        assert_eq!(clusters(&texts, 1.0), [vec![0, 1]]);
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use color_eyre::{eyre::bail, Result};
+use futures::future::BoxFuture;
+use roxmltree::{Document, Node};
+
+use crate::{html, http_client::Service, input::UrlEntry};
+
+/// How many levels of nested sitemap indexes are followed
+const MAX_SITEMAP_DEPTH: usize = 3;
+
+/// The contents of a sitemap or feed
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    /// A sitemap (including Google News sitemaps), RSS feed or Atom feed, listing pages
+    Pages(Vec<UrlEntry>),
+    /// A sitemap index, listing other sitemaps
+    SitemapIndex(Vec<String>),
+}
+
+/// Load a sitemap, sitemap index, RSS feed or Atom feed, and return every page it lists
+///
+/// Sitemap indexes are followed, so the pages of every sitemap they list are returned. Everything
+/// is fetched under the given service's rate limiter
+pub fn discover(url: &str, service: Service) -> BoxFuture<'_, Result<Vec<UrlEntry>>> {
+    discover_nested(url, service, 0)
+}
+
+fn discover_nested(
+    url: &str,
+    service: Service,
+    depth: usize,
+) -> BoxFuture<'_, Result<Vec<UrlEntry>>> {
+    Box::pin(async move {
+        let xml = html::load_html(url, service).await?;
+
+        match parse(&xml)? {
+            Source::Pages(pages) => Ok(pages),
+            Source::SitemapIndex(_) if depth >= MAX_SITEMAP_DEPTH => {
+                bail!("sitemap indexes nested more than {MAX_SITEMAP_DEPTH} deep at {url}")
+            }
+            Source::SitemapIndex(sitemaps) => {
+                let mut pages = Vec::new();
+
+                for sitemap in sitemaps {
+                    pages.extend(discover_nested(&sitemap, service, depth + 1).await?);
+                }
+
+                Ok(pages)
+            }
+        }
+    })
+}
+
+/// Parse a sitemap, sitemap index, RSS feed or Atom feed
+pub fn parse(xml: &str) -> Result<Source> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+
+    let source = match root.tag_name().name() {
+        "urlset" => Source::Pages(sitemap_pages(root)),
+        "sitemapindex" => Source::SitemapIndex(
+            children(root, "sitemap")
+                .filter_map(|sitemap| child_text(sitemap, "loc"))
+                .collect(),
+        ),
+        // RSS 2.0 keeps its items in a `<channel>`, RSS 1.0 keeps them next to it
+        "rss" | "RDF" => Source::Pages(rss_pages(root)),
+        "feed" => Source::Pages(atom_pages(root)),
+        other => bail!("unrecognised feed type `<{other}>`"),
+    };
+
+    Ok(source)
+}
+
+fn sitemap_pages(root: Node) -> Vec<UrlEntry> {
+    children(root, "url")
+        .filter_map(|url| {
+            let loc = child_text(url, "loc")?;
+
+            // only Google News sitemaps say when a page was published. `<lastmod>` is when it
+            // was last changed, so it isn't used
+            let published = children(url, "news")
+                .find_map(|news| child_text(news, "publication_date"))
+                .and_then(|date| normalise_date(&date));
+
+            Some(UrlEntry::new(loc, published))
+        })
+        .collect()
+}
+
+fn rss_pages(root: Node) -> Vec<UrlEntry> {
+    root.descendants()
+        .filter(|node| node.tag_name().name() == "item")
+        .filter_map(|item| {
+            let link = child_text(item, "link")?;
+            let published = ["pubDate", "date", "published"]
+                .into_iter()
+                .find_map(|name| child_text(item, name))
+                .and_then(|date| normalise_date(&date));
+
+            Some(UrlEntry::new(link, published))
+        })
+        .collect()
+}
+
+fn atom_pages(root: Node) -> Vec<UrlEntry> {
+    children(root, "entry")
+        .filter_map(|entry| {
+            // prefer the `alternate` link, which is the page itself
+            let links: Vec<_> = children(entry, "link").collect();
+            let link = links
+                .iter()
+                .find(|link| link.attribute("rel").unwrap_or("alternate") == "alternate")
+                .or(links.first())?
+                .attribute("href")?;
+
+            let published = ["published", "updated"]
+                .into_iter()
+                .find_map(|name| child_text(entry, name))
+                .and_then(|date| normalise_date(&date));
+
+            Some(UrlEntry::new(link.trim().to_string(), published))
+        })
+        .collect()
+}
+
+/// The child elements of a node with a given name, ignoring namespaces
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+/// The trimmed text of the first child element with a given name, if it isn't empty
+fn child_text(node: Node, name: &'static str) -> Option<String> {
+    let text = children(node, name).next()?.text()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Convert a date from a feed to RFC 3339 in UTC (or `YYYY-MM-DD`, if there is no time)
+///
+/// RSS uses RFC 2822 dates, while Atom and sitemaps use RFC 3339 (or a plain date)
+pub fn normalise_date(date: &str) -> Option<String> {
+    let date = date.trim();
+
+    let datetime = DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(date))
+        .ok();
+
+    if let Some(datetime) = datetime {
+        let utc = datetime.with_timezone(&Utc);
+        return Some(utc.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    }
+
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, published: Option<&str>) -> UrlEntry {
+        UrlEntry::new(url.to_string(), published.map(String::from))
+    }
+
+    #[test]
+    fn parses_rss() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <rss version="2.0">
+              <channel>
+                <title>BBC News 中文</title>
+                <link>https://www.bbc.com/zhongwen/simp</link>
+                <item>
+                  <title>标题</title>
+                  <link>https://www.bbc.com/zhongwen/simp/articles/c1</link>
+                  <pubDate>Mon, 07 Aug 2023 09:30:00 GMT</pubDate>
+                </item>
+                <item>
+                  <link> https://www.bbc.com/zhongwen/simp/articles/c2 </link>
+                </item>
+              </channel>
+            </rss>"#;
+
+        assert_eq!(
+            parse(xml).unwrap(),
+            Source::Pages(vec![
+                entry(
+                    "https://www.bbc.com/zhongwen/simp/articles/c1",
+                    Some("2023-08-07T09:30:00Z")
+                ),
+                entry("https://www.bbc.com/zhongwen/simp/articles/c2", None),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_atom() {
+        let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+              <link href="https://example.com/feed" rel="self"/>
+              <entry>
+                <link href="https://example.com/feed/c1" rel="self"/>
+                <link href="https://example.com/articles/c1" rel="alternate"/>
+                <published>2023-08-07T17:30:00+08:00</published>
+                <updated>2023-08-08T00:00:00Z</updated>
+              </entry>
+            </feed>"#;
+
+        assert_eq!(
+            parse(xml).unwrap(),
+            Source::Pages(vec![entry(
+                "https://example.com/articles/c1",
+                Some("2023-08-07T09:30:00Z")
+            )])
+        );
+    }
+
+    #[test]
+    fn parses_news_sitemap() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+                xmlns:news="http://www.google.com/schemas/sitemap-news/0.9">
+              <url>
+                <loc>https://www.bbc.com/zhongwen/simp/articles/c1</loc>
+                <lastmod>2023-09-01</lastmod>
+                <news:news>
+                  <news:publication_date>2023-08-07</news:publication_date>
+                </news:news>
+              </url>
+              <url>
+                <loc>https://www.bbc.com/zhongwen/simp/articles/c2</loc>
+                <lastmod>2023-09-01</lastmod>
+              </url>
+            </urlset>"#;
+
+        assert_eq!(
+            parse(xml).unwrap(),
+            Source::Pages(vec![
+                entry(
+                    "https://www.bbc.com/zhongwen/simp/articles/c1",
+                    Some("2023-08-07")
+                ),
+                entry("https://www.bbc.com/zhongwen/simp/articles/c2", None),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_sitemap_index() {
+        let xml = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <sitemap><loc>https://www.bbc.com/sitemaps/1.xml</loc></sitemap>
+              <sitemap><loc>https://www.bbc.com/sitemaps/2.xml</loc></sitemap>
+            </sitemapindex>"#;
+
+        assert_eq!(
+            parse(xml).unwrap(),
+            Source::SitemapIndex(vec![
+                "https://www.bbc.com/sitemaps/1.xml".to_string(),
+                "https://www.bbc.com/sitemaps/2.xml".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_html() {
+        assert!(parse("<html><body></body></html>").is_err());
+    }
+}
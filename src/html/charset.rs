@@ -0,0 +1,128 @@
+use std::sync::OnceLock;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use regex::bytes::Regex;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+/// How far into a page to look for a declaration of its encoding, as browsers do
+const PRESCAN_BYTES: usize = 1024;
+
+/// Decode the body of a page, in whichever encoding it's in
+///
+/// Like a browser, this goes by a byte order mark, then the `charset` in the `Content-Type` header,
+/// then a `<meta>` tag (or for feeds, the XML declaration) near the start of the page, and
+/// otherwise assumes UTF-8. Bytes that aren't valid in the encoding are replaced
+pub fn decode(headers: &HeaderMap, body: &[u8]) -> String {
+    let encoding = declared_in_header(headers)
+        .or_else(|| declared_in_page(body))
+        .unwrap_or(UTF_8);
+
+    // `decode` checks for a byte order mark before using the encoding
+    let (text, encoding, had_errors) = encoding.decode(body);
+    if had_errors {
+        tracing::debug!("page had bytes that aren't valid {}", encoding.name());
+    }
+
+    text.into_owned()
+}
+
+/// The encoding named by the `charset` parameter of the `Content-Type` header
+fn declared_in_header(headers: &HeaderMap) -> Option<&'static Encoding> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+
+    let charset = content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        let is_charset = name.trim().eq_ignore_ascii_case("charset");
+        is_charset.then(|| value.trim().trim_matches(['"', '\'']))
+    })?;
+
+    Encoding::for_label(charset.as_bytes())
+}
+
+/// The encoding named by a `<meta charset>` or `<meta http-equiv="Content-Type">` tag, or an XML
+/// declaration, near the start of the page
+fn declared_in_page(body: &[u8]) -> Option<&'static Encoding> {
+    static DECLARATION: OnceLock<Regex> = OnceLock::new();
+    let regex = DECLARATION.get_or_init(|| {
+        Regex::new(
+            r#"(?i-u)<meta\s[^>]*?charset\s*=\s*["']?([a-z0-9_:.+-]+)|<\?xml\s[^>]*?encoding\s*=\s*["']([a-z0-9_:.+-]+)"#,
+        )
+        .unwrap()
+    });
+
+    let start = &body[..body.len().min(PRESCAN_BYTES)];
+    let captures = regex.captures(start)?;
+    let label = captures.get(1).or_else(|| captures.get(2))?;
+    let encoding = Encoding::for_label(label.as_bytes())?;
+
+    // a page that could be read well enough to find the tag can't really be UTF-16, and
+    // `x-user-defined` is only meant for binary data, so browsers read these as below
+    Some(match encoding {
+        e if e == UTF_16LE || e == UTF_16BE => UTF_8,
+        e if e == X_USER_DEFINED => WINDOWS_1252,
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{BIG5, GB18030, GBK};
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    const TEXT: &str = "中国经济增长放缓";
+
+    /// [`TEXT`] in traditional characters, for Big5, which has no simplified ones
+    const TRADITIONAL_TEXT: &str = "中國經濟增長放緩";
+
+    fn headers(content_type: &str) -> HeaderMap {
+        HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap())])
+    }
+
+    fn page(head: &str, text: &str, encoding: &'static Encoding) -> Vec<u8> {
+        let html = format!("<html><head>{head}</head><body><p>{text}</p></body></html>");
+        encoding.encode(&html).0.into_owned()
+    }
+
+    #[test]
+    fn decodes_encoding_from_header() {
+        let body = page("", TEXT, GBK);
+
+        let text = decode(&headers("text/html; charset=\"GBK\""), &body);
+        assert!(text.contains(TEXT));
+
+        // without the header, it's read as UTF-8, and comes out garbled
+        let text = decode(&HeaderMap::new(), &body);
+        assert!(!text.contains(TEXT));
+    }
+
+    #[test]
+    fn decodes_encoding_from_meta_tags() {
+        let body = page(r#"<meta charset="gb2312">"#, TEXT, GBK);
+        assert!(decode(&headers("text/html"), &body).contains(TEXT));
+
+        let head = r#"<meta http-equiv="Content-Type" content="text/html; charset=big5">"#;
+        let body = page(head, TRADITIONAL_TEXT, BIG5);
+        assert!(decode(&HeaderMap::new(), &body).contains(TRADITIONAL_TEXT));
+
+        let xml =
+            r#"<?xml version="1.0" encoding="GB18030"?><rss><title>中国经济增长放缓</title></rss>"#;
+        let body = GB18030.encode(xml).0;
+        assert!(decode(&HeaderMap::new(), &body).contains(TEXT));
+    }
+
+    #[test]
+    fn prefers_bom_then_header() {
+        // the header outranks the meta tag
+        let body = page(r#"<meta charset="big5">"#, TEXT, GBK);
+        assert!(decode(&headers("text/html; charset=gbk"), &body).contains(TEXT));
+
+        // and a byte order mark outranks both
+        let mut body = b"\xEF\xBB\xBF".to_vec();
+        body.extend(page(r#"<meta charset="big5">"#, TEXT, UTF_8));
+        let text = decode(&headers("text/html; charset=gbk"), &body);
+        assert!(text.starts_with("<html>"));
+        assert!(text.contains(TEXT));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use tl::{NodeHandle, Parser, VDom};
+
+use super::selectors::{collapse_whitespace, select, SelectorProfile};
+
+/// A block of text from the body of an article
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentBlock {
+    pub kind: BlockKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Heading,
+    Paragraph,
+    Caption,
+}
+
+impl BlockKind {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Heading => "heading",
+            Self::Paragraph => "paragraph",
+            Self::Caption => "caption",
+        }
+    }
+
+    fn of_tag(name: &str) -> Option<Self> {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(Self::Heading),
+            "p" => Some(Self::Paragraph),
+            "figcaption" => Some(Self::Caption),
+            _ => None,
+        }
+    }
+}
+
+/// Find the headings, paragraphs and captions that make up the body of an article, in the order
+/// they appear
+///
+/// The body is the first element found by the profile's `content` selectors, and anything
+/// inside it matching one of its `boilerplate` selectors is left out
+pub fn article_content(dom: &VDom, profile: &SelectorProfile) -> Vec<ContentBlock> {
+    let parser = dom.parser();
+
+    let skip: HashSet<_> = profile
+        .boilerplate
+        .iter()
+        .filter_map(|selector| select(dom, selector))
+        .flatten()
+        .collect();
+
+    let root: Vec<_> = profile
+        .content
+        .iter()
+        .find_map(|selector| select(dom, selector)?.into_iter().next())
+        .map_or_else(|| dom.children().to_vec(), |root| vec![root]);
+
+    let mut blocks = Vec::new();
+    for node in root {
+        collect_blocks(node, parser, &skip, &mut blocks);
+    }
+
+    blocks
+}
+
+fn collect_blocks(
+    node: NodeHandle,
+    parser: &Parser,
+    skip: &HashSet<NodeHandle>,
+    blocks: &mut Vec<ContentBlock>,
+) {
+    if skip.contains(&node) {
+        return;
+    }
+
+    let Some(tag) = node.get(parser).and_then(|node| node.as_tag()) else {
+        return;
+    };
+
+    let name = tag.name().as_utf8_str().to_ascii_lowercase();
+
+    // a block's text includes anything nested inside it, so there's no need to look further
+    if let Some(kind) = BlockKind::of_tag(&name) {
+        let text = collapse_whitespace(&tag.inner_text(parser));
+        if !text.is_empty() {
+            blocks.push(ContentBlock { kind, text });
+        }
+        return;
+    }
+
+    for &child in tag.children().top().iter() {
+        collect_blocks(child, parser, skip, blocks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tl::ParserOptions;
+
+    use super::*;
+
+    #[test]
+    fn extracts_content_without_boilerplate() {
+        let html = r#"
+            <header role="banner"><p>BBC News 中文</p></header>
+            <main>
+              <nav><p>导航</p></nav>
+              <article>
+                <header><h1>中国经济增长放缓</h1></header>
+                <div><p>第一段，<b>加粗</b>。</p></div>
+                <figure><img src="a.jpg"><figcaption>图片说明</figcaption></figure>
+                <p>   </p>
+                <section aria-labelledby="related-content-heading">
+                  <h2>相关内容</h2>
+                  <p>相关链接</p>
+                </section>
+                <h2>小标题</h2>
+                <p>第二段</p>
+              </article>
+            </main>
+            <footer><p>版权</p></footer>"#;
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+
+        let blocks: Vec<_> = article_content(&dom, &SelectorProfile::default())
+            .into_iter()
+            .map(|block| (block.kind, block.text))
+            .collect();
+
+        assert_eq!(
+            blocks,
+            [
+                (BlockKind::Heading, "中国经济增长放缓".to_string()),
+                (BlockKind::Paragraph, "第一段，加粗。".to_string()),
+                (BlockKind::Caption, "图片说明".to_string()),
+                (BlockKind::Heading, "小标题".to_string()),
+                (BlockKind::Paragraph, "第二段".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_whole_page_without_content_element() {
+        let html = "<nav><p>导航</p></nav><div><p>正文</p></div>";
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+
+        let blocks = article_content(&dom, &SelectorProfile::default());
+        assert_eq!(
+            blocks,
+            [ContentBlock {
+                kind: BlockKind::Paragraph,
+                text: "正文".to_string()
+            }]
+        );
+    }
+}
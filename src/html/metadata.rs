@@ -0,0 +1,233 @@
+use serde_json::Value;
+use tl::VDom;
+
+use super::selectors::{collapse_whitespace, select, FieldSelector, SelectorProfile};
+
+/// Everything we can find out about an article from its HTML
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArticleMetadata {
+    /// The description found by the selector profile
+    pub description: Option<String>,
+    /// Where the description came from: a `meta`, `og` or `twitter` tag, or a `paragraph`
+    pub description_source: Option<String>,
+    /// The headline found by the selector profile, or the JSON-LD `headline`
+    pub headline: Option<String>,
+    pub og_title: Option<String>,
+    pub og_description: Option<String>,
+    pub twitter_description: Option<String>,
+    /// The text of the `<title>` element
+    pub page_title: Option<String>,
+    /// The URL in `<link rel="canonical">`
+    pub canonical_url: Option<String>,
+    /// When the article was published, exactly as the page gives it
+    pub published: Option<String>,
+    /// The section (or topic) the article was published under
+    pub section: Option<String>,
+    /// The author, or a comma separated list of authors
+    pub author: Option<String>,
+    /// The JSON-LD `NewsArticle` block, if the page has one
+    pub news_article: Option<Value>,
+}
+
+impl ArticleMetadata {
+    /// Read an article's metadata from its parsed HTML
+    ///
+    /// The fields the selector profile covers come from the profile, falling back to the JSON-LD
+    /// block. The rest come from the standard Open Graph, Twitter and `<link>` tags
+    pub fn extract(dom: &VDom, profile: &SelectorProfile) -> Self {
+        let news_article = news_article(dom);
+        let ld = |field: &str| news_article.as_ref().and_then(|ld| ld_text(ld.get(field)?));
+
+        let (description, description_source) = match profile.description(dom) {
+            Some((description, source)) => (Some(description), Some(description_source(source))),
+            None => (None, None),
+        };
+
+        Self {
+            description,
+            description_source,
+            headline: profile.title(dom).or_else(|| ld("headline")),
+            og_title: find(dom, r#"meta[property="og:title"]@content"#),
+            og_description: find(dom, r#"meta[property="og:description"]@content"#),
+            twitter_description: find(dom, r#"meta[name="twitter:description"]@content"#),
+            page_title: find(dom, "title"),
+            canonical_url: find(dom, r#"link[rel="canonical"]@href"#),
+            published: profile.date(dom).or_else(|| ld("datePublished")),
+            section: profile.section(dom).or_else(|| ld("articleSection")),
+            author: profile.author(dom).or_else(|| ld("author")),
+            news_article,
+        }
+    }
+}
+
+/// A stable label for the selector a description was found by, which doesn't change when the
+/// selector profile does
+///
+/// Attributes are read from meta tags, so are labelled by the kind of tag: `og` for Open Graph,
+/// `twitter` for Twitter cards, and `meta` for anything else. A description read from an element's
+/// text is labelled `paragraph`
+fn description_source(selector: &FieldSelector) -> String {
+    let label = match &selector.attribute {
+        None => "paragraph",
+        Some(_) if selector.selector.contains("og:") => "og",
+        Some(_) if selector.selector.contains("twitter:") => "twitter",
+        Some(_) => "meta",
+    };
+
+    label.to_string()
+}
+
+fn find(dom: &VDom, selector: &str) -> Option<String> {
+    selector.parse::<FieldSelector>().ok()?.find(dom)
+}
+
+/// Find the JSON-LD block describing the article
+///
+/// A page can have several JSON-LD scripts, each holding a single item, a list of items, or a
+/// `@graph` of items, so every item is checked for a type ending in `Article` (`NewsArticle`,
+/// `ReportageNewsArticle`, etc.). Scripts that aren't valid JSON are skipped
+fn news_article(dom: &VDom) -> Option<Value> {
+    let parser = dom.parser();
+
+    // `tl` can't parse `/` or `+` in an attribute selector, so the type is checked by hand
+    select(dom, "script[type]")?
+        .into_iter()
+        .filter_map(|node| {
+            let tag = node.get(parser)?.as_tag()?;
+            let ty = tag.attributes().get("type")??.as_utf8_str();
+            if !ty.trim().eq_ignore_ascii_case("application/ld+json") {
+                return None;
+            }
+
+            serde_json::from_str::<Value>(&tag.inner_text(parser)).ok()
+        })
+        .find_map(find_article)
+}
+
+fn find_article(value: Value) -> Option<Value> {
+    match value {
+        Value::Array(items) => items.into_iter().find_map(find_article),
+        Value::Object(mut item) => {
+            if let Some(graph) = item.remove("@graph") {
+                return find_article(graph);
+            }
+
+            let is_article = match item.get("@type") {
+                Some(Value::String(ty)) => ty.ends_with("Article"),
+                Some(Value::Array(types)) => types
+                    .iter()
+                    .any(|ty| ty.as_str().is_some_and(|ty| ty.ends_with("Article"))),
+                _ => false,
+            };
+
+            is_article.then_some(Value::Object(item))
+        }
+        _ => None,
+    }
+}
+
+/// The text of a JSON-LD value, which may be a string, an object with a `name`, or a list of
+/// either (which are joined with commas)
+fn ld_text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => collapse_whitespace(s),
+        Value::Object(item) => ld_text(item.get("name")?)?,
+        Value::Array(items) => items
+            .iter()
+            .filter_map(ld_text)
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => return None,
+    };
+
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use tl::ParserOptions;
+
+    use super::*;
+
+    const ARTICLE: &str = r#"<!DOCTYPE html>
+        <html lang="zh-Hans">
+          <head>
+            <title>中国经济 - BBC News 中文</title>
+            <meta name="description" content="描述">
+            <meta property="og:title" content="中国经济增长放缓">
+            <meta property="og:description" content="OG 描述">
+            <meta name="twitter:description" content="Twitter 描述">
+            <link rel="canonical" href="https://www.bbc.com/zhongwen/simp/articles/c1">
+            <script type="application/ld+json">{ not json</script>
+            <script type="application/ld+json">
+              {
+                "@context": "http://schema.org",
+                "@graph": [
+                  { "@type": "WebPage", "name": "BBC" },
+                  {
+                    "@type": "ReportageNewsArticle",
+                    "headline": "JSON-LD 标题",
+                    "datePublished": "2023-08-07T09:00:00Z",
+                    "articleSection": ["中国", "经济"],
+                    "author": [{ "@type": "Person", "name": "记者甲" }, { "name": "记者乙" }]
+                  }
+                ]
+              }
+            </script>
+          </head>
+          <body><h1>中国经济增长放缓</h1></body>
+        </html>"#;
+
+    #[test]
+    fn extracts_metadata() {
+        let dom = tl::parse(ARTICLE, ParserOptions::default()).unwrap();
+        let metadata = ArticleMetadata::extract(&dom, &SelectorProfile::default());
+
+        assert_eq!(metadata.description.as_deref(), Some("描述"));
+        assert_eq!(metadata.description_source.as_deref(), Some("meta"));
+        assert_eq!(metadata.headline.as_deref(), Some("中国经济增长放缓"));
+        assert_eq!(metadata.og_description.as_deref(), Some("OG 描述"));
+        assert_eq!(
+            metadata.twitter_description.as_deref(),
+            Some("Twitter 描述")
+        );
+        assert_eq!(
+            metadata.page_title.as_deref(),
+            Some("中国经济 - BBC News 中文")
+        );
+        assert_eq!(
+            metadata.canonical_url.as_deref(),
+            Some("https://www.bbc.com/zhongwen/simp/articles/c1")
+        );
+
+        // these aren't in the meta tags, so come from the JSON-LD block
+        assert_eq!(metadata.published.as_deref(), Some("2023-08-07T09:00:00Z"));
+        assert_eq!(metadata.section.as_deref(), Some("中国, 经济"));
+        assert_eq!(metadata.author.as_deref(), Some("记者甲, 记者乙"));
+        assert_eq!(
+            metadata.news_article.unwrap()["@type"],
+            "ReportageNewsArticle"
+        );
+    }
+
+    #[test]
+    fn labels_description_sources() {
+        let label = |selector: &str| description_source(&selector.parse().unwrap());
+
+        assert_eq!(label(r#"meta[name="description"]@content"#), "meta");
+        assert_eq!(label(r#"meta[property="og:description"]@content"#), "og");
+        assert_eq!(
+            label(r#"meta[name="twitter:description"]@content"#),
+            "twitter"
+        );
+        assert_eq!(label("main p"), "paragraph");
+    }
+
+    #[test]
+    fn missing_metadata_is_none() {
+        let dom = tl::parse("<p>hello</p>", ParserOptions::default()).unwrap();
+        let metadata = ArticleMetadata::extract(&dom, &SelectorProfile::default());
+
+        assert_eq!(metadata, ArticleMetadata::default());
+    }
+}
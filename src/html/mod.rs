@@ -0,0 +1,151 @@
+use color_eyre::{
+    eyre::{bail, ensure},
+    Result,
+};
+use reqwest::StatusCode;
+use tl::ParserOptions;
+
+use crate::{
+    http_client::{self, Clients, Service},
+    robots,
+    snapshot::SnapshotStore,
+    sources::{BbcChinese, NewsSource},
+};
+
+mod charset;
+mod content;
+mod metadata;
+mod selectors;
+
+pub use content::{BlockKind, ContentBlock};
+pub use metadata::ArticleMetadata;
+pub use selectors::{FieldSelector, LinkPattern, SelectorProfile};
+
+/// Load the given BBC URL, parse the HTML, and return the page's description, as found by the
+/// selector profile
+pub async fn description_of_page(url: &str, profile: &SelectorProfile) -> Result<String> {
+    BbcChinese::new(profile.clone()).description(url).await
+}
+
+/// Load the given BBC URL, parse the HTML, and return everything we can find out about the article
+pub async fn metadata_of_page(url: &str, profile: &SelectorProfile) -> Result<ArticleMetadata> {
+    Ok(article_of_page(url, profile).await?.metadata)
+}
+
+/// An article's metadata and the text of its body
+#[derive(Debug, Clone, Default)]
+pub struct Article {
+    pub metadata: ArticleMetadata,
+    pub content: Vec<ContentBlock>,
+}
+
+/// Load the given BBC URL, parse the HTML, and return the article's metadata and body
+pub async fn article_of_page(url: &str, profile: &SelectorProfile) -> Result<Article> {
+    BbcChinese::new(profile.clone()).article(url).await
+}
+
+/// Load the HTML of a news site's page, waiting for the given service's rate limiter first
+///
+/// If a snapshot archive is enabled, the page is saved to it, or in offline mode, the latest
+/// snapshot of the page is used instead of fetching it. A page that's already in the archive is
+/// fetched with a conditional request, using its `ETag` and `Last-Modified` headers, and if the
+/// site says it hasn't changed, the snapshot is used (and saved again, as it was at this time).
+/// Pages that the site's `robots.txt` disallows are skipped, and never fetched.
+///
+/// The page is decoded from whichever encoding its headers or `<meta>` tags say it's in, so
+/// older sites served in GBK or Big5 come out right
+pub async fn load_html(url: &str, service: Service) -> Result<String> {
+    let response = match SnapshotStore::get() {
+        Some(store) if SnapshotStore::is_offline() => match store.latest(url).await? {
+            Some(snapshot) => {
+                tracing::debug!("using snapshot of {url} from {}", snapshot.fetched_at);
+                snapshot.response
+            }
+            None => bail!("no snapshot of {url}"),
+        },
+        store => {
+            if !robots::is_allowed(url, service).await? {
+                tracing::warn!("skipping {url}, which is disallowed by robots.txt");
+                bail!("{url} is disallowed by robots.txt");
+            }
+
+            let previous = match store {
+                Some(store) => store.latest(url).await?,
+                None => None,
+            };
+            let previous = previous.filter(|snapshot| snapshot.response.status.is_success());
+            let conditional = previous
+                .as_ref()
+                .map(|snapshot| snapshot.conditional_headers())
+                .unwrap_or_default();
+
+            let client = Clients::get().news();
+            let request = || client.get(url).headers(conditional.clone());
+            let response = http_client::send_limited(service, request).await?;
+
+            let response = match previous {
+                Some(previous) if response.status == StatusCode::NOT_MODIFIED => {
+                    tracing::debug!("{url} hasn't changed since {}", previous.fetched_at);
+                    previous.response
+                }
+                _ => response,
+            };
+
+            if let Some(store) = store {
+                store.save(url, &response).await?;
+            }
+
+            response
+        }
+    };
+
+    let status = response.status;
+    ensure!(status.is_success(), "{url} responded with {status}");
+
+    Ok(charset::decode(&response.headers, &response.body))
+}
+
+/// Return all the article links on a topic page, as found by the selector profile
+pub fn article_links(html: &str, profile: &SelectorProfile) -> Result<Vec<String>> {
+    let parsed = tl::parse(html, ParserOptions::default())?;
+    Ok(profile.links(&parsed))
+}
+
+/// The number of pages in a paginated topic, read from its pagination control
+///
+/// Returns `None` if the page doesn't have a pagination control
+pub fn page_count(html: &str, profile: &SelectorProfile) -> Result<Option<usize>> {
+    let parsed = tl::parse(html, ParserOptions::default())?;
+    Ok(profile.page_count(&parsed))
+}
+
+/// Read an article's metadata from its HTML
+pub fn extract_metadata(html: &str, profile: &SelectorProfile) -> Result<ArticleMetadata> {
+    let parsed = tl::parse(html, ParserOptions::default())?;
+    Ok(ArticleMetadata::extract(&parsed, profile))
+}
+
+/// Read an article's metadata and body from its HTML
+pub fn extract_article(html: &str, profile: &SelectorProfile) -> Result<Article> {
+    let parsed = tl::parse(html, ParserOptions::default())?;
+
+    Ok(Article {
+        metadata: ArticleMetadata::extract(&parsed, profile),
+        content: content::article_content(&parsed, profile),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let html = r#"<head><meta name="description" content="foo"><\head>"#;
+        let parsed = extract_metadata(html, &SelectorProfile::default())
+            .unwrap()
+            .description
+            .unwrap();
+        assert_eq!(parsed, "foo");
+    }
+}
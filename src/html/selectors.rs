@@ -0,0 +1,602 @@
+use std::{collections::HashSet, path::Path, sync::OnceLock};
+
+use color_eyre::{eyre::ensure, Result};
+use regex::Regex;
+use serde::Deserialize;
+use tl::{NodeHandle, VDom};
+
+/// A set of selectors describing where to find things on a news site's pages
+///
+/// Each field is a list of fallbacks, tried in order, and the first one that finds anything is
+/// used. This means a profile can keep working after the site is redesigned, as long as one of
+/// its fallbacks still matches.
+///
+/// Selectors are CSS selectors, optionally followed by `@attribute` to take the value of an
+/// attribute rather than the text of the element (e.g. `meta[name="description"]@content`).
+/// Descendant combinators are supported (e.g. `main a`), and `>` is treated the same way
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SelectorProfile {
+    /// Selectors for links to articles on a topic page
+    pub links: Vec<String>,
+    /// Patterns that the `href` of an article link must match
+    ///
+    /// Links that don't match any of these are ignored, which filters out navigation, adverts,
+    /// etc. If this is empty, every link is kept
+    pub link_patterns: Vec<LinkPattern>,
+    /// Selectors for the links in a topic page's pagination control
+    ///
+    /// The number of pages is the highest `page=<n>` found in their `href`s
+    pub pagination: Vec<String>,
+    /// Selectors for an article's description, in order of preference
+    ///
+    /// The selector that found the description is recorded alongside it, so descriptions from
+    /// fallbacks can be told apart from the preferred source
+    pub description: Vec<FieldSelector>,
+    /// The maximum length, in characters, of a description read from an element's text (such as
+    /// a paragraph), rather than from an attribute
+    ///
+    /// Longer text is cut at the last sentence end that fits, or at the limit if there isn't one
+    pub description_max_chars: usize,
+    pub title: Vec<FieldSelector>,
+    pub date: Vec<FieldSelector>,
+    /// Selectors for the element holding an article's body, the first one found is used
+    ///
+    /// If none of these are found, the whole page is used
+    pub content: Vec<String>,
+    /// Selectors for blocks inside the article's body that aren't part of the article, such as
+    /// navigation and related links, which are left out of its content
+    pub boilerplate: Vec<String>,
+    /// Selectors for the section (or topic) an article was published under
+    pub section: Vec<FieldSelector>,
+    pub author: Vec<FieldSelector>,
+}
+
+impl Default for SelectorProfile {
+    /// The built-in profile for BBC Chinese
+    ///
+    /// Rather than the generated class names that BBC uses for styling (which change whenever
+    /// the site is redeployed), this looks for links whose URLs look like articles
+    fn default() -> Self {
+        let selectors = |list: &[&str]| list.iter().map(|s| s.parse().unwrap()).collect();
+
+        Self {
+            links: vec!["main a[href]".into(), "a[href]".into()],
+            link_patterns: vec![
+                // e.g. `/zhongwen/articles/c4n...`, `/zhongwen/simp/articles/c4n...`
+                LinkPattern::new(r"/zhongwen/(?:[^/?#]+/)*articles/[^/?#]+"),
+                // e.g. `/zhongwen/simp/world-66414696`
+                LinkPattern::new(r"/zhongwen/(?:simp|trad)/[a-z-]+-\d+(?:$|[?#])"),
+            ],
+            pagination: vec!["nav a[href]".into(), "a[href]".into()],
+            description: selectors(&[
+                r#"meta[name="description"]@content"#,
+                r#"meta[property="og:description"]@content"#,
+                r#"meta[name="twitter:description"]@content"#,
+                "main p",
+                "article p",
+            ]),
+            description_max_chars: 200,
+            title: selectors(&[r#"meta[property="og:title"]@content"#, "h1", "title"]),
+            date: selectors(&[
+                "time[datetime]@datetime",
+                r#"meta[property="article:published_time"]@content"#,
+            ]),
+            content: vec!["article".into(), "main".into()],
+            boilerplate: [
+                "nav",
+                "footer",
+                "aside",
+                "form",
+                "script",
+                "style",
+                "noscript",
+                // the site header, but not the `<header>` at the top of an article, which holds
+                // its headline
+                r#"[role="banner"]"#,
+                r#"[role="navigation"]"#,
+                r#"[role="complementary"]"#,
+                // BBC's "related content", "top stories", etc. sections
+                "section[aria-labelledby]",
+            ]
+            .map(String::from)
+            .into(),
+            section: selectors(&[r#"meta[property="article:section"]@content"#]),
+            author: selectors(&[
+                r#"meta[name="author"]@content"#,
+                r#"meta[property="article:author"]@content"#,
+            ]),
+        }
+    }
+}
+
+impl SelectorProfile {
+    /// Load a profile from a given json5 file
+    ///
+    /// Any field that is left out keeps its value from the built-in profile
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(path)?;
+        let profile: Self = json5::from_str(&s)?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Check that every selector can be parsed
+    pub fn validate(&self) -> Result<()> {
+        let fields = self
+            .description
+            .iter()
+            .chain(&self.title)
+            .chain(&self.date)
+            .chain(&self.section)
+            .chain(&self.author);
+        let selectors = self
+            .links
+            .iter()
+            .chain(&self.pagination)
+            .chain(&self.content)
+            .chain(&self.boilerplate)
+            .chain(fields.map(|field| &field.selector));
+
+        for selector in selectors {
+            let parts = split_selector(selector);
+            let valid = !parts.is_empty()
+                && parts
+                    .iter()
+                    .all(|part| tl::parse_query_selector(part).is_some());
+
+            ensure!(valid, "invalid selector: {selector}");
+        }
+
+        Ok(())
+    }
+
+    /// Find all the article links on a page, in the order they appear, without duplicates
+    pub fn links(&self, dom: &VDom) -> Vec<String> {
+        for selector in &self.links {
+            let Some(nodes) = select(dom, selector) else {
+                continue;
+            };
+
+            let mut links = Vec::new();
+
+            for node in nodes {
+                let Some(tag) = node.get(dom.parser()).and_then(|node| node.as_tag()) else {
+                    continue;
+                };
+                let Some(Some(href)) = tag.attributes().get("href") else {
+                    continue;
+                };
+
+                let href = href.as_utf8_str().to_string();
+                if self.is_article_link(&href) && !links.contains(&href) {
+                    links.push(href);
+                }
+            }
+
+            if !links.is_empty() {
+                return links;
+            }
+        }
+
+        vec![]
+    }
+
+    /// The number of pages in a paginated topic, or `None` if there is no pagination control
+    pub fn page_count(&self, dom: &VDom) -> Option<usize> {
+        static PAGE: OnceLock<Regex> = OnceLock::new();
+        let regex = PAGE.get_or_init(|| Regex::new(r"[?&]page=(\d+)").unwrap());
+
+        self.pagination.iter().find_map(|selector| {
+            select(dom, selector)?
+                .into_iter()
+                .filter_map(|node| {
+                    let tag = node.get(dom.parser())?.as_tag()?;
+                    let href = tag.attributes().get("href")??.as_utf8_str();
+                    regex.captures(&href)?[1].parse::<usize>().ok()
+                })
+                .max()
+        })
+    }
+
+    /// Whether a URL matches one of the article link patterns (or there are no patterns)
+    pub fn is_article_link(&self, href: &str) -> bool {
+        self.link_patterns.is_empty()
+            || self
+                .link_patterns
+                .iter()
+                .any(|pattern| pattern.0.is_match(href))
+    }
+
+    /// Find the description, and the selector that found it
+    pub fn description(&self, dom: &VDom) -> Option<(String, &FieldSelector)> {
+        self.description.iter().find_map(|selector| {
+            let value = selector.find(dom)?;
+            let value = match selector.attribute {
+                Some(_) => value,
+                None => truncate_text(&value, self.description_max_chars),
+            };
+
+            Some((value, selector))
+        })
+    }
+
+    pub fn title(&self, dom: &VDom) -> Option<String> {
+        first_match(&self.title, dom)
+    }
+
+    pub fn date(&self, dom: &VDom) -> Option<String> {
+        first_match(&self.date, dom)
+    }
+
+    pub fn section(&self, dom: &VDom) -> Option<String> {
+        first_match(&self.section, dom)
+    }
+
+    pub fn author(&self, dom: &VDom) -> Option<String> {
+        first_match(&self.author, dom)
+    }
+}
+
+/// Try each selector in turn, returning the first non-empty value
+fn first_match(selectors: &[FieldSelector], dom: &VDom) -> Option<String> {
+    selectors.iter().find_map(|selector| selector.find(dom))
+}
+
+/// Find every element matching a selector, in the order they appear
+///
+/// `tl` parses descendant combinators (e.g. `main a`) but never matches them, so the selector is
+/// split up, and each part is matched within the elements found by the part before it
+pub(super) fn select(dom: &VDom, selector: &str) -> Option<Vec<NodeHandle>> {
+    let parser = dom.parser();
+    let parts = split_selector(selector);
+    let (first, rest) = parts.split_first()?;
+
+    let mut handles: Vec<_> = dom.query_selector(first)?.collect();
+
+    for part in rest {
+        let mut seen = HashSet::new();
+        let mut next = Vec::new();
+
+        for handle in handles {
+            let Some(tag) = handle.get(parser).and_then(|node| node.as_tag()) else {
+                continue;
+            };
+
+            for found in tag.query_selector(parser, part)? {
+                if seen.insert(found) {
+                    next.push(found);
+                }
+            }
+        }
+
+        handles = next;
+    }
+
+    Some(handles)
+}
+
+/// Split a selector on its combinators (whitespace or `>`), ignoring any inside quotes or
+/// attribute brackets
+fn split_selector(selector: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut in_brackets = false;
+
+    for (i, c) in selector.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => in_brackets = true,
+            (None, ']') => in_brackets = false,
+            (None, c) if !in_brackets && (c.is_whitespace() || c == '>') => {
+                if let Some(start) = start.take() {
+                    parts.push(&selector[start..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        start.get_or_insert(i);
+    }
+
+    if let Some(start) = start {
+        parts.push(&selector[start..]);
+    }
+
+    parts
+}
+
+/// A CSS selector, and optionally the attribute to read from the element it matches
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FieldSelector {
+    pub selector: String,
+    pub attribute: Option<String>,
+}
+
+impl FieldSelector {
+    /// Get the value from the first element this selector matches that has a non-empty value
+    pub fn find(&self, dom: &VDom) -> Option<String> {
+        let parser = dom.parser();
+
+        select(dom, &self.selector)?.into_iter().find_map(|node| {
+            let node = node.get(parser)?;
+
+            let value = match &self.attribute {
+                Some(attribute) => {
+                    let value = node.as_tag()?.attributes().get(attribute.as_str())??;
+                    value.as_utf8_str().to_string()
+                }
+                None => node.inner_text(parser).to_string(),
+            };
+
+            let value = collapse_whitespace(&value);
+            (!value.is_empty()).then_some(value)
+        })
+    }
+}
+
+impl std::fmt::Display for FieldSelector {
+    /// Write the selector in the same `css@attribute` form it is parsed from
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.attribute {
+            Some(attribute) => write!(f, "{}@{attribute}", self.selector),
+            None => write!(f, "{}", self.selector),
+        }
+    }
+}
+
+impl std::str::FromStr for FieldSelector {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (selector, attribute) = match split_attribute(s) {
+            Some((selector, attribute)) => (selector, Some(attribute.to_string())),
+            None => (s, None),
+        };
+
+        let selector = selector.trim().to_string();
+        ensure!(!selector.is_empty(), "empty selector in `{s}`");
+
+        Ok(Self {
+            selector,
+            attribute,
+        })
+    }
+}
+
+/// Split a trailing `@attribute` off a selector
+///
+/// Only an `@` outside quotes and attribute brackets counts, and only if it's followed by a valid
+/// attribute name, so one in an attribute value (e.g. `a[href^="mailto:x@y"]`) stays part of the
+/// selector
+fn split_attribute(s: &str) -> Option<(&str, &str)> {
+    let mut at = None;
+    let mut quote = None;
+    let mut in_brackets = false;
+
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => in_brackets = true,
+            (None, ']') => in_brackets = false,
+            (None, '@') if !in_brackets => at = Some(i),
+            _ => {}
+        }
+    }
+
+    let (selector, attribute) = s.split_at(at?);
+    let attribute = attribute[1..].trim();
+
+    let is_name = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.');
+    (!attribute.is_empty() && attribute.chars().all(is_name)).then_some((selector, attribute))
+}
+
+impl TryFrom<String> for FieldSelector {
+    type Error = color_eyre::Report;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// A regular expression that article URLs must match
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LinkPattern(Regex);
+
+impl LinkPattern {
+    /// Make a pattern from a regular expression that is known to be valid
+    ///
+    /// # Panics
+    ///
+    /// If the regular expression is invalid
+    pub fn new(pattern: &str) -> Self {
+        Self(Regex::new(pattern).unwrap())
+    }
+}
+
+impl TryFrom<String> for LinkPattern {
+    type Error = regex::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Regex::new(&s).map(Self)
+    }
+}
+
+/// Cut text down to at most `max_chars` characters, ending at a sentence end if possible
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    let Some((cut, _)) = text.char_indices().nth(max_chars) else {
+        return text.to_string();
+    };
+    let text = &text[..cut];
+
+    match text.rfind(['。', '！', '？', '.', '!', '?']) {
+        Some(end) => {
+            let end = end + text[end..].chars().next().unwrap().len_utf8();
+            text[..end].to_string()
+        }
+        None => text.to_string(),
+    }
+}
+
+/// Replace any run of whitespace with a single space, and trim the ends
+pub(super) fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use tl::ParserOptions;
+
+    use super::*;
+
+    const TOPIC_PAGE: &str = r#"
+        <nav><a href="/zhongwen/simp/articles/nav-link">nav</a></nav>
+        <main>
+          <a class="bbc-abc123" href="/zhongwen/simp/articles/c1">1</a>
+          <a href="/zhongwen/simp/topics/other">topic</a>
+          <a class="bbc-abc123" href="https://www.bbc.com/zhongwen/articles/c2/trad">2</a>
+          <a href="/zhongwen/simp/world-66414696">3</a>
+          <a class="bbc-abc123" href="/zhongwen/simp/articles/c1">1 again</a>
+        </main>"#;
+
+    #[test]
+    fn default_profile_matches_url_patterns() {
+        let dom = tl::parse(TOPIC_PAGE, ParserOptions::default()).unwrap();
+        let links = SelectorProfile::default().links(&dom);
+
+        assert_eq!(
+            links,
+            [
+                "/zhongwen/simp/articles/c1",
+                "https://www.bbc.com/zhongwen/articles/c2/trad",
+                "/zhongwen/simp/world-66414696",
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_later_selectors() {
+        let profile: SelectorProfile = json5::from_str(
+            r#"{
+                links: ["a.bbc-uk8dsi", "a.bbc-abc123"],
+                link_patterns: [],
+                title: ["h2", "h1"],
+            }"#,
+        )
+        .unwrap();
+        profile.validate().unwrap();
+
+        let dom = tl::parse(TOPIC_PAGE, ParserOptions::default()).unwrap();
+        assert_eq!(
+            profile.links(&dom),
+            [
+                "/zhongwen/simp/articles/c1",
+                "https://www.bbc.com/zhongwen/articles/c2/trad"
+            ]
+        );
+
+        let html = "<h1>  Big \n news </h1>";
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+        assert_eq!(profile.title(&dom).unwrap(), "Big news");
+    }
+
+    #[test]
+    fn reads_attributes() {
+        let html = r#"<head><time datetime="2023-08-07T09:00:00Z">7 August</time></head>"#;
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+
+        let date = SelectorProfile::default().date(&dom).unwrap();
+        assert_eq!(date, "2023-08-07T09:00:00Z");
+    }
+
+    #[test]
+    fn description_falls_back_to_a_truncated_paragraph() {
+        let profile = SelectorProfile {
+            description_max_chars: 12,
+            ..Default::default()
+        };
+
+        let html = r#"
+            <meta property="og:description" content="">
+            <main><p>第一句话很短。第二句话比较长一些。</p><p>第二段。</p></main>"#;
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+
+        let (description, source) = profile.description(&dom).unwrap();
+        assert_eq!(description, "第一句话很短。");
+        assert_eq!(source.to_string(), "main p");
+
+        let html =
+            r#"<meta name="twitter:description" content="推特描述，不会被截断的。"><p>段落</p>"#;
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+
+        let (description, source) = profile.description(&dom).unwrap();
+        assert_eq!(description, "推特描述，不会被截断的。");
+        assert_eq!(
+            source.to_string(),
+            r#"meta[name="twitter:description"]@content"#
+        );
+    }
+
+    #[test]
+    fn truncates_text() {
+        assert_eq!(truncate_text("短", 10), "短");
+        assert_eq!(truncate_text("One. Two. Three.", 10), "One. Two.");
+        assert_eq!(truncate_text("没有句号的长句子", 4), "没有句号");
+    }
+
+    #[test]
+    fn counts_pages() {
+        let html = r#"
+            <nav aria-label="Page">
+              <a href="?page=1">1</a>
+              <a href="?page=2">2</a>
+              <a href="/zhongwen/simp/topics/c1?page=40">40</a>
+              <a href="?page=2">Next</a>
+            </nav>"#;
+        let dom = tl::parse(html, ParserOptions::default()).unwrap();
+        assert_eq!(SelectorProfile::default().page_count(&dom), Some(40));
+
+        let dom = tl::parse(TOPIC_PAGE, ParserOptions::default()).unwrap();
+        assert_eq!(SelectorProfile::default().page_count(&dom), None);
+    }
+
+    #[test]
+    fn splits_selectors_on_combinators() {
+        assert_eq!(split_selector("main a[href]"), ["main", "a[href]"]);
+        assert_eq!(split_selector("ul > li  a"), ["ul", "li", "a"]);
+        assert_eq!(
+            split_selector(r#"div meta[name="a > b c"]"#),
+            ["div", r#"meta[name="a > b c"]"#]
+        );
+    }
+
+    #[test]
+    fn parses_field_selectors() {
+        let field: FieldSelector = r#"meta[name="description"]@content"#.parse().unwrap();
+        assert_eq!(field.selector, r#"meta[name="description"]"#);
+        assert_eq!(field.attribute.as_deref(), Some("content"));
+
+        // an `@` in an attribute value is part of the selector
+        let field: FieldSelector = r#"a[href^="mailto:x@y"]"#.parse().unwrap();
+        assert_eq!(field.selector, r#"a[href^="mailto:x@y"]"#);
+        assert_eq!(field.attribute, None);
+
+        let field: FieldSelector = r#"a[href^="mailto:x@y"] @ href"#.parse().unwrap();
+        assert_eq!(field.selector, r#"a[href^="mailto:x@y"]"#);
+        assert_eq!(field.attribute.as_deref(), Some("href"));
+    }
+
+    #[test]
+    fn rejects_invalid_selectors() {
+        let profile: SelectorProfile = json5::from_str(r#"{ links: ["a[href"] }"#).unwrap();
+        assert!(profile.validate().is_err());
+    }
+}
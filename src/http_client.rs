@@ -1,53 +1,438 @@
-use std::{process::Command, sync::OnceLock};
+use std::{fmt, process::Command, sync::OnceLock, time::Duration};
 
+use color_eyre::{eyre::eyre, Report, Result};
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
-    {Client, ClientBuilder},
+    Proxy, RequestBuilder, StatusCode, {Client, ClientBuilder},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    audit::{AuditLog, AuditRequest},
+    cassette::Cassette,
+    rate_limiter::RateLimiters,
 };
 
 static X_GOOG_USER_PROJECT: HeaderName = HeaderName::from_static("x-goog-user-project");
 
 static CLIENTS: OnceLock<Clients> = OnceLock::new();
 
+/// The user agent sent to the news sites we scrape, unless the config says otherwise
+///
+/// This says who we are, rather than pretending to be a browser, so site owners can tell what is
+/// crawling them. Its first word is also the name we look for in `robots.txt`
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "dissertation/",
+    env!("CARGO_PKG_VERSION"),
+    " (academic research crawler)"
+);
+
+/// The number of times a request is sent before giving up, while the API keeps saying we're
+/// sending too many requests
+const MAX_ATTEMPTS: usize = 5;
+
+/// The number of times a request is sent before giving up, while it keeps timing out
+const MAX_TIMEOUT_ATTEMPTS: usize = 2;
+
+/// How long a request waits for the whole response, unless the config says otherwise
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// ChatGPT can take a while to write a long translation
+const CHATGPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long a request waits for a connection to be made, unless the config says otherwise
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// A struct that contains all the HTTP clients pre-configured with the requried authentication
 /// data
 ///
-/// Note, google trends doesn't need special config, since all auth is done through the 
+/// The authenticated clients are only built when first used, so programs that never talk to an
+/// API don't need its credentials.
+///
+/// Note, google trends doesn't need special config, since all auth is done through the API key in
+/// the query string
 pub struct Clients {
-    pub bbc: Client,
-    pub google_translate: Client,
-    pub chatgpt: Client,
+    user_agent: String,
+    settings: HttpSettings,
+    news: Client,
+    google_translate: OnceLock<Client>,
+    chatgpt: OnceLock<Client>,
+    trends: Client,
 }
 
 impl Clients {
+    /// Set up the global http clients, sending the given user agent to the sites we scrape
+    ///
+    /// This must be called before the first request is sent, otherwise [`DEFAULT_USER_AGENT`] and
+    /// the default settings are used
+    pub fn init(user_agent: &str, settings: &HttpSettings) -> Result<()> {
+        CLIENTS
+            .set(Self::new(user_agent, settings)?)
+            .map_err(|_| eyre!("the http clients have already been set up"))
+    }
+
     /// Get a reference to the global http client instance
     pub fn get() -> &'static Clients {
-        CLIENTS.get_or_init(|| {
-            let bbc = bbc_client();
-            let google_translate = google_translate_client();
-            let chatgpt = chatgpt_client();
-
-            Clients {
-                bbc,
-                google_translate,
-                chatgpt,
+        CLIENTS.get_or_init(|| Self::new(DEFAULT_USER_AGENT, &HttpSettings::default()).unwrap())
+    }
+
+    fn new(user_agent: &str, settings: &HttpSettings) -> Result<Self> {
+        // the authenticated clients are built later, so check their settings now, while a
+        // mistake can still be reported properly
+        settings
+            .google_translate
+            .builder(DEFAULT_TIMEOUT)?
+            .build()?;
+        settings.chatgpt.builder(CHATGPT_TIMEOUT)?.build()?;
+
+        Ok(Clients {
+            user_agent: user_agent.to_string(),
+            settings: settings.clone(),
+            news: settings
+                .news
+                .builder(DEFAULT_TIMEOUT)?
+                .user_agent(user_agent)
+                .build()?,
+            google_translate: OnceLock::new(),
+            chatgpt: OnceLock::new(),
+            trends: settings.trends.builder(DEFAULT_TIMEOUT)?.build()?,
+        })
+    }
+
+    /// The user agent sent to the sites we scrape
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    /// The client for fetching pages from news sites
+    pub fn news(&self) -> &Client {
+        &self.news
+    }
+
+    pub fn google_translate(&self) -> &Client {
+        self.google_translate
+            .get_or_init(|| google_translate_client(&self.settings.google_translate))
+    }
+
+    pub fn chatgpt(&self) -> &Client {
+        self.chatgpt
+            .get_or_init(|| chatgpt_client(&self.settings.chatgpt))
+    }
+
+    pub fn trends(&self) -> &Client {
+        &self.trends
+    }
+}
+
+/// The settings for each of the HTTP clients
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSettings {
+    /// The client for the news sites we scrape (and their `robots.txt`)
+    pub news: ClientSettings,
+    pub google_translate: ClientSettings,
+    pub chatgpt: ClientSettings,
+    pub trends: ClientSettings,
+}
+
+/// How a single HTTP client connects, with times in seconds
+///
+/// Every field is optional, and anything left out keeps its default value
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientSettings {
+    /// How long to wait for the whole response before giving up (defaults to 30 seconds, or 120
+    /// for ChatGPT)
+    pub timeout: Option<f64>,
+    /// How long to wait for a connection to be made (defaults to 10 seconds)
+    pub connect_timeout: Option<f64>,
+    /// The most idle connections to keep open to each host (defaults to no limit)
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept open (defaults to 90 seconds)
+    pub idle_timeout: Option<f64>,
+    /// Only use HTTP/1.1, for servers (or proxies) that handle HTTP/2 badly
+    pub http1_only: bool,
+    /// How often to ping an HTTP/2 connection to keep it alive (defaults to never)
+    pub http2_keep_alive: Option<f64>,
+    /// Let HTTP/2's flow control window grow to fit the connection
+    pub http2_adaptive_window: bool,
+    /// The URL of a proxy to send every request through, e.g. `http://proxy.example.com:3128`
+    ///
+    /// Without one, the `HTTPS_PROXY` and `HTTP_PROXY` environment variables are used (except for
+    /// hosts in `NO_PROXY`)
+    pub proxy: Option<String>,
+}
+
+impl ClientSettings {
+    /// A client builder with these settings, for a service whose responses usually come well
+    /// within `default_timeout`
+    fn builder(&self, default_timeout: Duration) -> Result<ClientBuilder> {
+        let seconds = |secs: f64| {
+            Duration::try_from_secs_f64(secs)
+                .map_err(|_| eyre!("invalid number of seconds: {secs}"))
+        };
+
+        let timeout = self.timeout.map(seconds).transpose()?;
+        let connect_timeout = self.connect_timeout.map(seconds).transpose()?;
+
+        let mut builder = ClientBuilder::new()
+            .timeout(timeout.unwrap_or(default_timeout))
+            .connect_timeout(connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+            .http2_adaptive_window(self.http2_adaptive_window);
+
+        if let Some(max) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            builder = builder.pool_idle_timeout(seconds(idle_timeout)?);
+        }
+        if self.http1_only {
+            builder = builder.http1_only();
+        }
+        if let Some(interval) = self.http2_keep_alive {
+            builder = builder.http2_keep_alive_interval(seconds(interval)?);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// The error returned when a service doesn't respond in time
+///
+/// This is returned inside the [`Report`], so it can be told apart from other errors with
+/// `report.downcast_ref::<Timeout>()`. A timeout is usually down to one slow connection, so the
+/// request is worth sending again
+#[derive(Debug)]
+pub struct Timeout {
+    pub service: Service,
+    pub url: String,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let service = self.service.to_str();
+        write!(
+            f,
+            "timed out waiting for {service} to respond to {}",
+            self.url
+        )
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+static ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+
+/// The base URLs of the APIs we send requests to
+///
+/// Each of these can be overridden by an environment variable, which lets the tests point the
+/// program at a local mock server. BBC pages have no base URL, since their URLs are part of the
+/// input
+pub struct Endpoints {
+    pub chatgpt: String,
+    pub google_translate: String,
+    pub trends: String,
+}
+
+impl Endpoints {
+    /// Get a reference to the global set of endpoints
+    pub fn get() -> &'static Endpoints {
+        ENDPOINTS.get_or_init(|| {
+            let endpoint = |var: &str, default: &str| {
+                let url = std::env::var(var).unwrap_or_else(|_| default.to_string());
+                url.trim_end_matches('/').to_string()
+            };
+
+            Endpoints {
+                chatgpt: endpoint("OPENAI_BASE_URL", "https://api.openai.com/v1"),
+                google_translate: endpoint(
+                    "GOOGLE_TRANSLATE_BASE_URL",
+                    "https://translate.googleapis.com/v3beta1",
+                ),
+                trends: endpoint(
+                    "TRENDS_BASE_URL",
+                    "https://www.googleapis.com/trends/v1beta",
+                ),
             }
         })
     }
 }
 
-fn bbc_client() -> Client {
-    const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/115.0.0.0 Safari/537.36";
+/// The external services that we send requests to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Service {
+    Bbc,
+    Voa,
+    Chatgpt,
+    GoogleTranslate,
+    Trends,
+}
+
+impl Service {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Service::Bbc => "bbc",
+            Service::Voa => "voa",
+            Service::Chatgpt => "chatgpt",
+            Service::GoogleTranslate => "google_translate",
+            Service::Trends => "trends",
+        }
+    }
+}
 
-    ClientBuilder::new().user_agent(USER_AGENT).build().unwrap()
+/// A response whose body has been read into memory
+///
+/// Buffering the body means the same response can be parsed by the caller and written to the
+/// audit log
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// The body of the response as a string, replacing any invalid UTF-8
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Parse the body of the response as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// Send a request to the given service and read the whole response
+///
+/// If the audit log is enabled, the request and its response (or the error, if the request
+/// failed) are recorded there.
+///
+/// If a cassette is enabled, the response is either saved to it, or served from it without
+/// touching the network
+pub async fn send(service: Service, request: RequestBuilder) -> Result<HttpResponse> {
+    let (client, request) = request.build_split();
+    let request = request?;
+
+    let audit = AuditLog::get().map(|log| (log, AuditRequest::new(service, &request)));
+
+    let result = match Cassette::get() {
+        None => execute(service, &client, request).await,
+        Some(cassette) => {
+            let key = Cassette::key(&request);
+
+            if Cassette::is_replaying() {
+                cassette.replay(key).await
+            } else {
+                let result = execute(service, &client, request).await;
+                if let Ok(response) = &result {
+                    cassette.record(key, response).await?;
+                }
+                result
+            }
+        }
+    };
+
+    if let Some((audit_log, audit_request)) = audit {
+        // losing an entry from the log shouldn't lose the response it was about
+        if let Err(e) = audit_log.record(audit_request, &result).await {
+            tracing::warn!("couldn't record a request to the audit log: {e}");
+        }
+    }
+
+    if let Ok(response) = &result {
+        if !Cassette::is_replaying() {
+            RateLimiters::get().observe(service, response);
+        }
+    }
+
+    result
+}
+
+/// Wait for the service's rate limiter, then send a request built by `request`
+///
+/// If the service responds with `429 Too Many Requests`, the rate limiter backs off, and the
+/// request is sent again, up to [`MAX_ATTEMPTS`] times in total. After that, the `429` response
+/// is returned.
+///
+/// A request that times out is sent again, up to [`MAX_TIMEOUT_ATTEMPTS`] times in total, before
+/// the [`Timeout`] error is returned
+pub async fn send_limited(
+    service: Service,
+    request: impl Fn() -> RequestBuilder,
+) -> Result<HttpResponse> {
+    let mut attempt = 1;
+    let mut timeouts = 0;
+
+    loop {
+        let _permit = RateLimiters::get().wait(service).await;
+        let response = match send(service, request()).await {
+            Ok(response) => response,
+            Err(e) if e.is::<Timeout>() && timeouts + 1 < MAX_TIMEOUT_ATTEMPTS => {
+                tracing::warn!("{e}, retrying");
+                timeouts += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        if response.status != StatusCode::TOO_MANY_REQUESTS || attempt == MAX_ATTEMPTS {
+            return Ok(response);
+        }
+
+        attempt += 1;
+    }
+}
+
+async fn execute(
+    service: Service,
+    client: &Client,
+    request: reqwest::Request,
+) -> Result<HttpResponse> {
+    let url = request.url().to_string();
+    let error = |e: reqwest::Error| {
+        if e.is_timeout() {
+            Report::new(Timeout {
+                service,
+                url: url.clone(),
+            })
+        } else {
+            e.into()
+        }
+    };
+
+    let response = client.execute(request).await.map_err(error)?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await.map_err(error)?.to_vec();
+
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Read a credential from the environment
+///
+/// When replaying a cassette, no requests reach the real APIs, so a missing credential is
+/// replaced with its name
+pub(crate) fn credential(name: &str) -> String {
+    match std::env::var(name) {
+        Ok(value) => value,
+        Err(_) if Cassette::is_replaying() => name.to_string(),
+        Err(e) => panic!("{name}: {e}"),
+    }
 }
 
 /// Get an HTTP client authenticated for use with Google APIs
-fn google_translate_client() -> Client {
+fn google_translate_client(settings: &ClientSettings) -> Client {
     let secret = google_secret();
     let authorization = HeaderValue::from_str(&format!("Bearer {secret}")).unwrap();
 
-    let project_id = std::env::var("GCLOUD_PROJECT_ID").unwrap();
+    let project_id = credential("GCLOUD_PROJECT_ID");
     let project_id = HeaderValue::from_str(&project_id).unwrap();
 
     let headers = HeaderMap::from_iter([
@@ -55,14 +440,26 @@ fn google_translate_client() -> Client {
         (X_GOOG_USER_PROJECT.clone(), project_id),
     ]);
 
-    ClientBuilder::new()
+    settings
+        .builder(DEFAULT_TIMEOUT)
+        .unwrap()
         .default_headers(headers)
         .build()
         .unwrap()
 }
 
-// Get the application secret from the `gcloud` CLI tool
+// Get the application secret from the `gcloud` CLI tool, unless one has been provided in the
+// `GCLOUD_ACCESS_TOKEN` env var
 fn google_secret() -> String {
+    if let Ok(token) = std::env::var("GCLOUD_ACCESS_TOKEN") {
+        return token;
+    }
+
+    // the `gcloud` tool may not even be installed when replaying
+    if Cassette::is_replaying() {
+        return "GCLOUD_ACCESS_TOKEN".to_string();
+    }
+
     let output = Command::new("gcloud")
         .args(["auth", "print-access-token"])
         .output()
@@ -72,13 +469,15 @@ fn google_secret() -> String {
 }
 
 /// Get an HTTP client authenticated for use with the ChatGPT client
-fn chatgpt_client() -> Client {
-    let openai_key = std::env::var("OPENAI_KEY").unwrap();
+fn chatgpt_client(settings: &ClientSettings) -> Client {
+    let openai_key = credential("OPENAI_KEY");
     let authorization = format!("Bearer {openai_key}").parse().unwrap();
 
     let headers = HeaderMap::from_iter([(AUTHORIZATION, authorization)]);
 
-    ClientBuilder::new()
+    settings
+        .builder(CHATGPT_TIMEOUT)
+        .unwrap()
         .default_headers(headers)
         .build()
         .unwrap()
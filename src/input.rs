@@ -1,8 +1,9 @@
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fs::File,
     io::{BufRead, BufReader},
-    path::Path, ops::Deref,
+    ops::Deref,
+    path::Path,
 };
 
 use color_eyre::Result;
@@ -16,11 +17,64 @@ pub fn read_file_lines<P: AsRef<Path>>(path: P) -> Result<Vec<String>, std::io::
     BufReader::new(file).lines().collect()
 }
 
+/// A URL to process, and when the page was published (if known)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlEntry {
+    pub url: String,
+    pub published: Option<String>,
+    /// The name of the topic the page was found on
+    pub topic: Option<String>,
+    /// The category of that topic (e.g. `politics` or `business`)
+    pub category: Option<String>,
+}
+
+impl UrlEntry {
+    pub fn new(url: String, published: Option<String>) -> Self {
+        Self {
+            url,
+            published,
+            topic: None,
+            category: None,
+        }
+    }
+
+    pub fn with_topic(self, topic: Option<String>, category: Option<String>) -> Self {
+        Self {
+            topic,
+            category,
+            ..self
+        }
+    }
+}
+
+/// Read a list of URLs, where each line is a URL, optionally followed by tab separated fields: the
+/// date the page was published, the name of the topic it was found on, and that topic's category
+///
+/// Any of the fields can be left empty. Blank lines are ignored
+pub fn read_url_list<P: AsRef<Path>>(path: P) -> Result<Vec<UrlEntry>> {
+    let lines = read_file_lines(path)?;
+
+    let entries = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split('\t').map(str::trim);
+            let url = fields.next().unwrap_or_default().to_string();
+            let mut next = || fields.next().filter(|s| !s.is_empty()).map(String::from);
+            let (published, topic, category) = (next(), next(), next());
+
+            UrlEntry::new(url, published).with_topic(topic, category)
+        })
+        .collect();
+
+    Ok(entries)
+}
+
 /// A container for a set of prompts that we will give to chatgpt
 ///
 /// This preserves the name of the prompt, so it can be used when writing the header for the CSV
-/// file
-pub struct ChatgptPrompts(HashMap<String, Prompt>);
+/// file. Prompts are kept in order of their names, so the columns are in the same order every run
+pub struct ChatgptPrompts(BTreeMap<String, Prompt>);
 
 impl ChatgptPrompts {
     /// Load prompts from a given json5 file
@@ -33,7 +87,7 @@ impl ChatgptPrompts {
 
 // allow access to the inner data
 impl Deref for ChatgptPrompts {
-    type Target = HashMap<String, Prompt>;
+    type Target = BTreeMap<String, Prompt>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -0,0 +1,174 @@
+use clap::ValueEnum;
+use whatlang::Lang;
+
+use crate::script::Script;
+
+/// The languages that articles can be translated from
+///
+/// These are the languages of the larger BBC World Service sites that Google Translate and the
+/// language identifier both support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    Arabic,
+    Bengali,
+    Chinese,
+    French,
+    Hindi,
+    Japanese,
+    Korean,
+    Persian,
+    Portuguese,
+    Russian,
+    Spanish,
+    Turkish,
+    Ukrainian,
+    Urdu,
+    Vietnamese,
+}
+
+impl Language {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Language::Arabic => "arabic",
+            Language::Bengali => "bengali",
+            Language::Chinese => "chinese",
+            Language::French => "french",
+            Language::Hindi => "hindi",
+            Language::Japanese => "japanese",
+            Language::Korean => "korean",
+            Language::Persian => "persian",
+            Language::Portuguese => "portuguese",
+            Language::Russian => "russian",
+            Language::Spanish => "spanish",
+            Language::Turkish => "turkish",
+            Language::Ukrainian => "ukrainian",
+            Language::Urdu => "urdu",
+            Language::Vietnamese => "vietnamese",
+        }
+    }
+
+    /// The language's name in English, which is what `{language}` in a prompt is replaced with
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Arabic => "Arabic",
+            Language::Bengali => "Bengali",
+            Language::Chinese => "Chinese",
+            Language::French => "French",
+            Language::Hindi => "Hindi",
+            Language::Japanese => "Japanese",
+            Language::Korean => "Korean",
+            Language::Persian => "Persian",
+            Language::Portuguese => "Portuguese",
+            Language::Russian => "Russian",
+            Language::Spanish => "Spanish",
+            Language::Turkish => "Turkish",
+            Language::Ukrainian => "Ukrainian",
+            Language::Urdu => "Urdu",
+            Language::Vietnamese => "Vietnamese",
+        }
+    }
+
+    /// The code that Google Translate uses for the language
+    ///
+    /// Chinese has a code for each script, and text in neither script is treated as simplified
+    pub fn google_code(self, script: Option<Script>) -> &'static str {
+        match self {
+            Language::Arabic => "ar",
+            Language::Bengali => "bn",
+            Language::Chinese => script.unwrap_or(Script::Simplified).language_code(),
+            Language::French => "fr",
+            Language::Hindi => "hi",
+            Language::Japanese => "ja",
+            Language::Korean => "ko",
+            Language::Persian => "fa",
+            Language::Portuguese => "pt",
+            Language::Russian => "ru",
+            Language::Spanish => "es",
+            Language::Turkish => "tr",
+            Language::Ukrainian => "uk",
+            Language::Urdu => "ur",
+            Language::Vietnamese => "vi",
+        }
+    }
+
+    /// Whether a language that a text was identified as is this one
+    pub fn is(self, lang: Lang) -> bool {
+        self.lang() == lang
+    }
+
+    /// The language as the language identifier knows it
+    fn lang(self) -> Lang {
+        match self {
+            Language::Arabic => Lang::Ara,
+            Language::Bengali => Lang::Ben,
+            Language::Chinese => Lang::Cmn,
+            Language::French => Lang::Fra,
+            Language::Hindi => Lang::Hin,
+            Language::Japanese => Lang::Jpn,
+            Language::Korean => Lang::Kor,
+            Language::Persian => Lang::Pes,
+            Language::Portuguese => Lang::Por,
+            Language::Russian => Lang::Rus,
+            Language::Spanish => Lang::Spa,
+            Language::Turkish => Lang::Tur,
+            Language::Ukrainian => Lang::Ukr,
+            Language::Urdu => Lang::Urd,
+            Language::Vietnamese => Lang::Vie,
+        }
+    }
+}
+
+/// Identify the language a text is written in, from its script and the letter combinations it
+/// uses
+///
+/// Text that's a mix of languages counts as whichever most of its letters are in. Returns `None`
+/// if the text has nothing to go on, such as if it's only numbers and punctuation
+pub fn identify(text: &str) -> Option<Lang> {
+    whatlang::detect_lang(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifies_languages() {
+        assert_eq!(
+            identify("中国经济增长放缓，政府宣布新的刺激措施。"),
+            Some(Lang::Cmn)
+        );
+        assert_eq!(
+            identify(
+                "Экономический рост в Китае замедлился, правительство объявило о новых мерах."
+            ),
+            Some(Lang::Rus)
+        );
+        assert_eq!(identify("2023"), None);
+    }
+
+    #[test]
+    fn identifies_mixed_text_by_its_main_language() {
+        // a headline with a few English words in it is still Chinese...
+        assert_eq!(identify("BBC 调查：中国经济增长放缓"), Some(Lang::Cmn));
+
+        // ...but a page that's mostly English isn't
+        assert_eq!(
+            identify(
+                "英汉对照 The government announced a series of new economic stimulus measures."
+            ),
+            Some(Lang::Eng)
+        );
+    }
+
+    #[test]
+    fn picks_google_codes() {
+        assert_eq!(Language::Chinese.google_code(None), "zh-CN");
+        assert_eq!(
+            Language::Chinese.google_code(Some(Script::Traditional)),
+            "zh-TW"
+        );
+        assert_eq!(Language::Russian.google_code(None), "ru");
+        assert_eq!(Language::Chinese.name(), "Chinese");
+        assert!(Language::Russian.is(Lang::Rus));
+    }
+}
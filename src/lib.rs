@@ -1,7 +1,22 @@
+pub mod audit;
+pub mod cassette;
+pub mod config;
+pub mod dates;
+pub mod duplicates;
+pub mod feeds;
 pub mod html;
 mod http_client;
 pub mod input;
+pub mod language;
 pub mod output;
-mod rate_limiter;
+pub mod rate_limiter;
+pub mod robots;
 pub mod scoring;
+pub mod script;
+pub mod seen;
+pub mod snapshot;
+pub mod sources;
 pub mod translate;
+pub mod urls;
+
+pub use http_client::{ClientSettings, HttpSettings, Service, Timeout};
@@ -2,28 +2,90 @@ use std::io::Write;
 
 use color_eyre::Result;
 use csv::Writer;
+use whatlang::Lang;
 
-use crate::{input::ChatgptPrompts, scoring::TranslationScores, translate::Translations};
+use crate::{
+    html::ArticleMetadata,
+    input::{ChatgptPrompts, UrlEntry},
+    scoring::TranslationScores,
+    script::Script,
+    translate::Translations,
+};
 
 #[derive(Default)]
 pub struct CsvRow {
+    /// The news outlet the page is from (e.g. `bbc`)
+    pub source: &'static str,
     pub url: String,
+    /// The name and category of the topic the page was found on, from the URL list
+    pub topic: Option<String>,
+    pub category: Option<String>,
+    pub metadata: ArticleMetadata,
+    /// Which version of the page's description this is, in the snapshot archive's history of it
+    pub description_version: Option<usize>,
+    /// The kind of text this row is about (e.g. `description` or `paragraph`), and its position
+    /// among the texts of that unit in the article
+    pub unit: Option<(&'static str, usize)>,
+    /// The script the text was written in on the page, if it could be told
+    pub script: Option<Script>,
+    /// The language the text was identified as, if it could be told
+    pub language: Option<Lang>,
     pub translations: Option<Translations>,
     pub scores: Option<TranslationScores>,
 }
 
 impl CsvRow {
     pub fn new(
+        source: &'static str,
         url: String,
+        metadata: ArticleMetadata,
         translations: Option<Translations>,
         scores: Option<TranslationScores>,
     ) -> Self {
         Self {
+            source,
             url,
+            topic: None,
+            category: None,
+            metadata,
+            description_version: None,
+            unit: None,
+            script: None,
+            language: None,
             translations,
             scores,
         }
     }
+
+    pub fn with_topic(self, topic: Option<String>, category: Option<String>) -> Self {
+        Self {
+            topic,
+            category,
+            ..self
+        }
+    }
+
+    pub fn with_description_version(self, description_version: Option<usize>) -> Self {
+        Self {
+            description_version,
+            ..self
+        }
+    }
+
+    pub fn with_unit(self, kind: &'static str, index: usize) -> Self {
+        Self {
+            unit: Some((kind, index)),
+            ..self
+        }
+    }
+
+    pub fn with_script(self, script: Option<Script>) -> Self {
+        Self { script, ..self }
+    }
+
+    pub fn with_language(self, language: Option<Lang>) -> Self {
+        Self { language, ..self }
+    }
 }
 
 /// Write every row to the given CSV output
@@ -45,18 +107,75 @@ fn write_row(
     prompts: &ChatgptPrompts,
 ) -> Result<()> {
     writer.write_field(&row.url)?;
+    writer.write_field(row.source)?;
+    writer.write_field(row.topic.as_deref().unwrap_or_default())?;
+    writer.write_field(row.category.as_deref().unwrap_or_default())?;
+
+    match row.unit {
+        Some((kind, index)) => {
+            writer.write_field(kind)?;
+            writer.write_field(index.to_string())?;
+        }
+        None => {
+            writer.write_field("")?;
+            writer.write_field("")?;
+        }
+    }
+
+    let ArticleMetadata {
+        canonical_url,
+        headline,
+        published,
+        section,
+        author,
+        page_title,
+        og_title,
+        og_description,
+        twitter_description,
+        description_source,
+        news_article,
+        ..
+    } = &row.metadata;
+
+    let fields = [
+        canonical_url,
+        headline,
+        published,
+        section,
+        author,
+        page_title,
+        og_title,
+        og_description,
+        twitter_description,
+        description_source,
+    ];
+
+    for field in fields {
+        writer.write_field(field.as_deref().unwrap_or_default())?;
+    }
+
+    let news_article = news_article.as_ref().map(|ld| ld.to_string());
+    writer.write_field(news_article.unwrap_or_default())?;
+
+    let description_version = row.description_version.map(|version| version.to_string());
+    writer.write_field(description_version.unwrap_or_default())?;
+
+    writer.write_field(row.script.map_or("", Script::to_str))?;
+    writer.write_field(row.language.map_or("", |lang| lang.code()))?;
 
     if let Some(translations) = &row.translations {
         let Translations {
-            chinese_text,
+            source_text,
             google,
             chatgpt,
         } = translations;
 
-        writer.write_field(chinese_text)?;
+        writer.write_field(source_text)?;
         writer.write_field(google)?;
 
-        for (string, _region) in chatgpt.values() {
+        // look up each translation by prompt name, so they line up with the header
+        for name in prompts.keys() {
+            let (string, _region) = &chatgpt[name];
             writer.write_field(string)?;
         }
     } else {
@@ -85,8 +204,8 @@ fn write_row(
         write_float(*google_us_score)?;
         write_float(*google_uk_score)?;
 
-        for score in chatgpt_scores.values() {
-            write_float(*score)?;
+        for name in prompts.keys() {
+            write_float(chatgpt_scores[name])?;
         }
     } else {
         let num_missing_fields = prompts.len() + 2;
@@ -104,7 +223,26 @@ fn write_row(
 
 fn write_header(writer: &mut Writer<impl Write>, prompts: &ChatgptPrompts) -> Result<()> {
     writer.write_field("url")?;
-    writer.write_field("chinese_text")?;
+    writer.write_field("source")?;
+    writer.write_field("topic")?;
+    writer.write_field("category")?;
+    writer.write_field("unit")?;
+    writer.write_field("unit_index")?;
+    writer.write_field("canonical_url")?;
+    writer.write_field("headline")?;
+    writer.write_field("published")?;
+    writer.write_field("section")?;
+    writer.write_field("author")?;
+    writer.write_field("page_title")?;
+    writer.write_field("og_title")?;
+    writer.write_field("og_description")?;
+    writer.write_field("twitter_description")?;
+    writer.write_field("description_source")?;
+    writer.write_field("news_article")?;
+    writer.write_field("description_version")?;
+    writer.write_field("script")?;
+    writer.write_field("language")?;
+    writer.write_field("source_text")?;
     writer.write_field("google")?;
 
     for name in prompts.keys() {
@@ -123,3 +261,46 @@ fn write_header(writer: &mut Writer<impl Write>, prompts: &ChatgptPrompts) -> Re
 
     Ok(())
 }
+
+/// Write a list of URLs in the format read by [`crate::input::read_url_list`]
+///
+/// Fields after the URL are only written up to the last one that's known
+pub fn write_url_list(mut out: impl Write, entries: &[UrlEntry]) -> Result<()> {
+    for entry in entries {
+        let fields = [&entry.published, &entry.topic, &entry.category];
+        let known = fields
+            .iter()
+            .rposition(|field| field.is_some())
+            .map_or(0, |i| i + 1);
+
+        write!(out, "{}", entry.url)?;
+        for field in &fields[..known] {
+            write!(out, "\t{}", field.as_deref().unwrap_or_default())?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Write a report of the clusters of pages with near-duplicate descriptions, with a row for each
+/// page's URL and description
+///
+/// Clusters are numbered from 1, and the first page of each is its representative, which is the
+/// one kept when duplicates are dropped
+pub fn write_duplicates(out: impl Write, clusters: &[Vec<(&str, &str)>]) -> Result<()> {
+    let mut writer = Writer::from_writer(out);
+    writer.write_record(["cluster", "representative", "url", "description"])?;
+
+    for (i, cluster) in clusters.iter().enumerate() {
+        let cluster_number = (i + 1).to_string();
+
+        for (j, (url, description)) in cluster.iter().enumerate() {
+            let representative = if j == 0 { "true" } else { "false" };
+            writer.write_record([&cluster_number, representative, url, description])?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use std::{
+    num::NonZeroU32,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{
+    eyre::{ensure, eyre},
+    Result,
+};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use serde::Deserialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::{
+    cassette::Cassette,
+    http_client::{HttpResponse, Service},
+};
+
+mod throttle;
+
+use throttle::Throttle;
+
+static LIMITERS: OnceLock<RateLimiters> = OnceLock::new();
+
+/// A container for global rate limits shared between the whole application
+///
+/// On top of the fixed limits, each API is throttled further whenever it tells us we're sending
+/// too many requests (see [`Throttle`]).
+///
+/// When a cassette is being replayed, no requests are sent, so nothing needs to wait
+pub struct RateLimiters {
+    bbc: Limiter,
+    voa: Limiter,
+    chatgpt: Limiter,
+    google_translate: Limiter,
+    trends: Limiter,
+}
+
+impl RateLimiters {
+    /// Set up the global rate limiters with the given limits
+    ///
+    /// This must be called before the first request is sent, otherwise the default limits are used
+    pub fn init(limits: &RateLimits) -> Result<()> {
+        let limiters = Self {
+            bbc: Limiter::new(&limits.bbc)?,
+            voa: Limiter::new(&limits.voa)?,
+            chatgpt: Limiter::new(&limits.chatgpt)?,
+            google_translate: Limiter::new(&limits.google_translate)?,
+            trends: Limiter::new(&limits.trends)?,
+        };
+
+        LIMITERS
+            .set(limiters)
+            .map_err(|_| eyre!("the rate limiters have already been set up"))
+    }
+
+    /// Get a handle to the global rate limiter object
+    pub fn get() -> &'static Self {
+        LIMITERS.get_or_init(|| {
+            let limits = RateLimits::default();
+
+            Self {
+                bbc: Limiter::new(&limits.bbc).unwrap(),
+                voa: Limiter::new(&limits.voa).unwrap(),
+                chatgpt: Limiter::new(&limits.chatgpt).unwrap(),
+                google_translate: Limiter::new(&limits.google_translate).unwrap(),
+                trends: Limiter::new(&limits.trends).unwrap(),
+            }
+        })
+    }
+
+    /// Wait until a request can be sent to the given service
+    pub(crate) async fn wait(&'static self, service: Service) -> Permit {
+        self.limiter(service).wait().await
+    }
+
+    /// Update the throttling for a service based on a response it sent back
+    pub(crate) fn observe(&self, service: Service, response: &HttpResponse) {
+        let throttle = &self.limiter(service).throttle;
+        throttle.observe(response.status, &response.headers, Instant::now());
+    }
+
+    /// Never leave less than `delay` between requests to the given service, such as when a
+    /// site's `robots.txt` asks for a `Crawl-delay`
+    pub(crate) fn set_min_delay(&self, service: Service, delay: Duration) {
+        self.limiter(service).throttle.set_min_interval(delay);
+    }
+
+    fn limiter(&self, service: Service) -> &Limiter {
+        match service {
+            Service::Bbc => &self.bbc,
+            Service::Voa => &self.voa,
+            Service::Chatgpt => &self.chatgpt,
+            Service::GoogleTranslate => &self.google_translate,
+            Service::Trends => &self.trends,
+        }
+    }
+}
+
+/// Permission to send a request to a rate-limited API
+///
+/// If the API has a concurrency limit, this holds one of its slots until it is dropped, so it
+/// should be kept alive until the response has been read
+#[must_use]
+pub struct Permit {
+    _slot: Option<SemaphorePermit<'static>>,
+}
+
+/// The rate limit, concurrency limit and throttle for a single API
+struct Limiter {
+    rate: DefaultDirectRateLimiter,
+    concurrency: Option<Semaphore>,
+    throttle: Throttle,
+}
+
+impl Limiter {
+    fn new(limit: &Limit) -> Result<Self> {
+        let rate = RateLimiter::direct(limit.quota()?);
+        let concurrency = match limit.concurrency {
+            Some(0) => return Err(eyre!("concurrency limit must be at least 1")),
+            Some(n) => Some(Semaphore::new(n)),
+            None => None,
+        };
+
+        Ok(Self {
+            rate,
+            concurrency,
+            throttle: Throttle::new(),
+        })
+    }
+
+    async fn wait(&'static self) -> Permit {
+        if Cassette::is_replaying() {
+            return Permit { _slot: None };
+        }
+
+        let permit = match &self.concurrency {
+            // the semaphore is never closed, so this can't fail
+            Some(semaphore) => Some(semaphore.acquire().await.unwrap()),
+            None => None,
+        };
+
+        self.rate.until_ready().await;
+
+        if let Some(until) = self.throttle.reserve(Instant::now()) {
+            tokio::time::sleep_until(until.into()).await;
+        }
+
+        Permit { _slot: permit }
+    }
+}
+
+/// The limits for every rate-limited API
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    pub bbc: Limit,
+    pub voa: Limit,
+    pub chatgpt: Limit,
+    pub google_translate: Limit,
+    pub trends: Limit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            // this rate limit is mostly just a ballpark guess. it's fast enough for our purposes, and
+            // we never get hung up on with this setting
+            bbc: Limit::new(20, Period::Second),
+
+            // VOA's sites are smaller than BBC's, so go easier on them
+            voa: Limit::new(5, Period::Second),
+
+            // chatgpt rate limit is 90k tokens per minute
+            // a token is about 0.75 words (roughly)
+            // conservatively, this leaves us 60k words per minute
+            // a single description translation task is around 100 words, so 600 translations per
+            // minute
+            // being extra conservative, let's just call that 500
+            chatgpt: Limit::new(500, Period::Minute),
+
+            // the google translate quota is measured in characters (6 million per minute), which
+            // our descriptions come nowhere near, so this just stops us flooding the API
+            google_translate: Limit::new(600, Period::Minute),
+
+            // this number comes from the google trends api "quotas" page.
+            // It's actually 600, but let's be safe
+            trends: Limit::new(550, Period::Minute),
+        }
+    }
+}
+
+/// How often requests can be sent to a single API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    /// The number of requests allowed in each period
+    pub requests: u32,
+    pub per: Period,
+    /// The number of requests that can be sent at once after a quiet spell (defaults to `requests`)
+    #[serde(default)]
+    pub burst: Option<u32>,
+    /// The maximum number of requests that can be in flight at the same time (defaults to no limit)
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+}
+
+impl Limit {
+    fn new(requests: u32, per: Period) -> Self {
+        Self {
+            requests,
+            per,
+            burst: None,
+            concurrency: None,
+        }
+    }
+
+    fn quota(&self) -> Result<Quota> {
+        let requests = NonZeroU32::new(self.requests);
+        let requests = requests.ok_or_else(|| eyre!("rate limit must allow at least 1 request"))?;
+
+        let quota = match self.per {
+            Period::Second => Quota::per_second(requests),
+            Period::Minute => Quota::per_minute(requests),
+            Period::Hour => Quota::per_hour(requests),
+        };
+
+        match self.burst {
+            Some(burst) => {
+                ensure!(burst > 0, "burst size must be at least 1");
+                Ok(quota.allow_burst(NonZeroU32::new(burst).unwrap()))
+            }
+            None => Ok(quota),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Second,
+    Minute,
+    Hour,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_config_keeps_defaults() {
+        let limits: RateLimits = json5::from_str(
+            r#"{ chatgpt: { requests: 3000, per: "minute", burst: 100, concurrency: 50 } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(limits.chatgpt.requests, 3000);
+        assert_eq!(limits.chatgpt.burst, Some(100));
+        assert_eq!(limits.chatgpt.concurrency, Some(50));
+        assert_eq!(limits.trends.requests, 550);
+        assert_eq!(limits.bbc.per, Period::Second);
+    }
+
+    #[test]
+    fn rejects_zero_limits() {
+        assert!(Limiter::new(&Limit::new(0, Period::Second)).is_err());
+
+        let mut limit = Limit::new(10, Period::Second);
+        limit.concurrency = Some(0);
+        assert!(Limiter::new(&limit).is_err());
+    }
+}
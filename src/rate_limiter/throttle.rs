@@ -0,0 +1,291 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::{header::HeaderMap, StatusCode};
+
+/// The gap between requests right after an API first tells us to slow down
+const MIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The largest gap between requests, however many times we're told to slow down
+const MAX_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to pause when an API says we're sending too many requests, but not for how long
+///
+/// This doubles for each `429` in a row, up to [`MAX_BACKOFF`]
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Extra throttling on top of a fixed rate limit, driven by the responses we get back
+///
+/// When an API responds with `429 Too Many Requests`, every task sharing the throttle is paused
+/// (for as long as the API asks, if it says), and requests are then spaced out. Each successful
+/// response shrinks the gap by a little, so the rate slowly ramps back up to the fixed limit.
+pub(super) struct Throttle {
+    state: Mutex<State>,
+}
+
+struct State {
+    /// No requests can be sent before this point
+    paused_until: Option<Instant>,
+    /// The gap to leave between requests, zero when the API hasn't complained recently
+    interval: Duration,
+    /// The earliest point the next request can be sent, when `interval` is non-zero
+    next_slot: Instant,
+    /// How long to pause for the next `429` without a `Retry-After` header
+    backoff: Duration,
+    /// The smallest gap to ever leave between requests, such as a site's `Crawl-delay`
+    min_interval: Duration,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        let state = State {
+            paused_until: None,
+            interval: Duration::ZERO,
+            next_slot: Instant::now(),
+            backoff: DEFAULT_BACKOFF,
+            min_interval: Duration::ZERO,
+        };
+
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Reserve a point in time to send a request, returning it if the caller needs to wait
+    pub fn reserve(&self, now: Instant) -> Option<Instant> {
+        let mut state = self.state.lock().unwrap();
+        let mut slot = now;
+
+        match state.paused_until {
+            Some(until) if until > now => slot = until,
+            _ => state.paused_until = None,
+        }
+
+        let interval = state.interval.max(state.min_interval);
+        if !interval.is_zero() {
+            slot = slot.max(state.next_slot);
+            state.next_slot = slot + interval;
+        }
+
+        (slot > now).then_some(slot)
+    }
+
+    /// Never leave less than `interval` between requests, however the API responds
+    ///
+    /// If this is called more than once, the longest interval is kept
+    pub fn set_min_interval(&self, interval: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.min_interval = state.min_interval.max(interval);
+    }
+
+    /// Adjust the throttle based on a response from the API
+    pub fn observe(&self, status: StatusCode, headers: &HeaderMap, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let delay = retry_after(headers).unwrap_or(state.backoff);
+            tracing::warn!("rate limited, pausing for {delay:?}");
+
+            state.pause_until(now + delay);
+            state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+            state.interval = (state.interval * 2).clamp(MIN_INTERVAL, MAX_INTERVAL);
+            return;
+        }
+
+        if let Some(delay) = quota_exhausted(headers) {
+            state.pause_until(now + delay);
+        }
+
+        if status.is_success() {
+            state.backoff = DEFAULT_BACKOFF;
+
+            // ramp back up by shrinking the gap by 10% for each success
+            state.interval = state.interval.mul_f64(0.9);
+            if state.interval < Duration::from_millis(1) {
+                state.interval = Duration::ZERO;
+            }
+        }
+    }
+}
+
+impl State {
+    fn pause_until(&mut self, until: Instant) {
+        self.paused_until = Some(self.paused_until.map_or(until, |paused| paused.max(until)));
+    }
+}
+
+/// How long the API has asked us to wait, from the `Retry-After` header (or the millisecond
+/// version some APIs send)
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok();
+
+    if let Some(ms) = header("retry-after-ms").and_then(|s| s.trim().parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(ms / 1000.0));
+    }
+
+    // `Retry-After` can also be an HTTP date, in which case we fall back to our own backoff
+    let secs = header("retry-after")?.trim().parse::<f64>().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// If the `x-ratelimit-*` headers say a quota has been used up, how long until it resets
+///
+/// This follows OpenAI's format, where each quota has a `remaining` count and a `reset` duration
+fn quota_exhausted(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: String| headers.get(name)?.to_str().ok();
+
+    ["requests", "tokens"]
+        .into_iter()
+        .filter(|quota| {
+            header(format!("x-ratelimit-remaining-{quota}"))
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                == Some(0)
+        })
+        .filter_map(|quota| parse_duration(header(format!("x-ratelimit-reset-{quota}"))?))
+        .max()
+}
+
+/// Parse a duration like `1s`, `6m0s` or `20ms`
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = s.trim();
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(number_len);
+        let number: f64 = number.parse().ok()?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let secs = match unit {
+            "h" => number * 3600.0,
+            "m" => number * 60.0,
+            "s" => number,
+            "ms" => number / 1000.0,
+            _ => return None,
+        };
+
+        total += Duration::from_secs_f64(secs);
+        rest = tail;
+    }
+
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn pauses_for_retry_after() {
+        let throttle = Throttle::new();
+        let now = Instant::now();
+
+        let retry = headers(&[("retry-after", "5")]);
+        throttle.observe(StatusCode::TOO_MANY_REQUESTS, &retry, now);
+
+        // everyone waits for the pause, and are then spaced out
+        let first = throttle.reserve(now).unwrap();
+        let second = throttle.reserve(now).unwrap();
+        assert_eq!(first, now + Duration::from_secs(5));
+        assert_eq!(second, first + MIN_INTERVAL);
+    }
+
+    #[test]
+    fn backs_off_without_retry_after() {
+        let throttle = Throttle::new();
+        let now = Instant::now();
+
+        throttle.observe(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), now);
+        assert_eq!(throttle.reserve(now), Some(now + DEFAULT_BACKOFF));
+
+        let later = now + DEFAULT_BACKOFF;
+        throttle.observe(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), later);
+        assert!(throttle.reserve(later).unwrap() >= later + DEFAULT_BACKOFF * 2);
+    }
+
+    #[test]
+    fn ramps_back_up() {
+        let throttle = Throttle::new();
+        let now = Instant::now();
+
+        throttle.observe(
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after", "0")]),
+            now,
+        );
+        for _ in 0..100 {
+            throttle.observe(StatusCode::OK, &HeaderMap::new(), now);
+        }
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(throttle.reserve(later), None);
+        assert_eq!(throttle.reserve(later), None);
+    }
+
+    #[test]
+    fn keeps_min_interval() {
+        let throttle = Throttle::new();
+        let now = Instant::now();
+        let delay = Duration::from_secs(2);
+
+        throttle.set_min_interval(delay);
+        throttle.set_min_interval(Duration::from_secs(1));
+
+        assert_eq!(throttle.reserve(now), None);
+        assert_eq!(throttle.reserve(now), Some(now + delay));
+
+        // successes don't shrink the gap below the minimum
+        for _ in 0..100 {
+            throttle.observe(StatusCode::OK, &HeaderMap::new(), now);
+        }
+        assert_eq!(throttle.reserve(now), Some(now + delay * 2));
+    }
+
+    #[test]
+    fn pauses_when_quota_exhausted() {
+        let throttle = Throttle::new();
+        let now = Instant::now();
+
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "2s"),
+            ("x-ratelimit-remaining-tokens", "1000"),
+            ("x-ratelimit-reset-tokens", "1m"),
+        ]);
+        throttle.observe(StatusCode::OK, &exhausted, now);
+
+        assert_eq!(throttle.reserve(now), Some(now + Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use color_eyre::Result;
+use regex::Regex;
+use reqwest::Url;
+use tokio::sync::OnceCell;
+
+use crate::{
+    http_client::{self, Clients, Service},
+    rate_limiter::RateLimiters,
+};
+
+static ROBOTS: OnceLock<RobotsCache> = OnceLock::new();
+
+/// Check whether `robots.txt` lets us fetch a page
+///
+/// Each site's `robots.txt` is fetched the first time one of its pages is checked, and kept for
+/// the rest of the run. It is fetched under the given service's rate limiter, and if it asks for
+/// a `Crawl-delay`, that limiter leaves at least that long between requests from then on
+pub async fn is_allowed(url: &str, service: Service) -> Result<bool> {
+    let url = Url::parse(url)?;
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+
+    let site = ROBOTS.get_or_init(RobotsCache::default).site(&url);
+
+    // only the first task to ask for a site fetches its `robots.txt`, the rest wait for it
+    let robots = site
+        .get_or_try_init(|| {
+            let robots_url = format!("{}/robots.txt", url.origin().ascii_serialization());
+            fetch(robots_url, service)
+        })
+        .await?;
+
+    Ok(robots.is_allowed(&path))
+}
+
+/// The `robots.txt` of every site seen so far, keyed by origin
+#[derive(Default)]
+struct RobotsCache {
+    sites: Mutex<HashMap<String, Arc<OnceCell<Robots>>>>,
+}
+
+impl RobotsCache {
+    /// The (possibly not yet fetched) `robots.txt` of the site a URL is on
+    fn site(&self, url: &Url) -> Arc<OnceCell<Robots>> {
+        let origin = url.origin().ascii_serialization();
+        let mut sites = self.sites.lock().unwrap();
+        sites.entry(origin).or_default().clone()
+    }
+}
+
+/// Fetch and parse a site's `robots.txt`
+///
+/// Following RFC 9309, a missing `robots.txt` (any `4xx` status) allows everything, while a
+/// server error disallows everything, since the site may be struggling
+async fn fetch(url: String, service: Service) -> Result<Robots> {
+    let client = Clients::get().news();
+    let response = http_client::send_limited(service, || client.get(&url)).await?;
+
+    let robots = match response.status.as_u16() {
+        200..=299 => Robots::parse(&response.text(), Clients::get().user_agent()),
+        400..=499 => Robots::allow_all(),
+        status => {
+            tracing::warn!("{url} responded with {status}, treating every page as disallowed");
+            Robots::disallow_all()
+        }
+    };
+
+    if let Some(delay) = robots.crawl_delay {
+        tracing::info!("{url} asks for a crawl delay of {delay:?}");
+        RateLimiters::get().set_min_delay(service, delay);
+    }
+
+    Ok(robots)
+}
+
+/// The rules in a `robots.txt` that apply to us
+#[derive(Debug, Default)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    allow: bool,
+    /// The length of the path pattern, longer (more specific) patterns take priority
+    len: usize,
+    pattern: Regex,
+}
+
+impl Robots {
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule::new(false, "/")],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parse a `robots.txt`, keeping the rules for the given user agent
+    ///
+    /// Groups are matched on the first word of the user agent (e.g. `dissertation` for
+    /// `dissertation/0.1.0 (...)`), ignoring case. If no group names it, the `*` group is used
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let token = user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let groups = parse_groups(text);
+
+        let named: Vec<_> = groups
+            .iter()
+            .filter(|group| group.agents.contains(&token))
+            .collect();
+
+        let matching = if named.is_empty() {
+            groups
+                .iter()
+                .filter(|group| group.agents.iter().any(|agent| agent == "*"))
+                .collect()
+        } else {
+            named
+        };
+
+        let mut robots = Self::default();
+
+        // rules for the same user agent can be split over several groups
+        for group in matching {
+            robots.rules.extend(
+                group
+                    .rules
+                    .iter()
+                    .map(|(allow, path)| Rule::new(*allow, path)),
+            );
+            robots.crawl_delay = robots.crawl_delay.max(group.crawl_delay);
+        }
+
+        robots
+    }
+
+    /// Whether a path (including any query string) may be fetched
+    ///
+    /// The longest matching rule wins, and `Allow` wins a tie. A path no rule matches is allowed
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| rule.pattern.is_match(path))
+            .max_by_key(|rule| (rule.len, rule.allow))
+            .map_or(true, |rule| rule.allow)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+impl Rule {
+    /// Make a rule from a path pattern, where `*` matches anything and a trailing `$` anchors
+    /// the pattern to the end of the path
+    fn new(allow: bool, path: &str) -> Self {
+        let (path, anchored) = match path.strip_suffix('$') {
+            Some(path) => (path, true),
+            None => (path, false),
+        };
+
+        let pattern: Vec<_> = path.split('*').map(regex::escape).collect();
+        let mut pattern = format!("^{}", pattern.join(".*"));
+        if anchored {
+            pattern.push('$');
+        }
+
+        Self {
+            allow,
+            len: path.len(),
+            pattern: Regex::new(&pattern).unwrap(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Group {
+    /// The lowercased user agents this group applies to
+    agents: Vec<String>,
+    /// Each rule, and whether it allows or disallows its path
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+fn parse_groups(text: &str) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    // whether the last line was a `User-agent`, in which case the next one adds to its group
+    let mut in_agents = false;
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key.trim().to_ascii_lowercase().as_str() {
+            "user-agent" => {
+                if !in_agents {
+                    groups.push(Group::default());
+                }
+                in_agents = true;
+
+                let group = groups.last_mut().unwrap();
+                group.agents.push(value.to_ascii_lowercase());
+                continue;
+            }
+            // rules before the first `User-agent` don't belong to any group
+            _ if groups.is_empty() => {}
+            "allow" if !value.is_empty() => {
+                groups.last_mut().unwrap().rules.push((true, value.into()));
+            }
+            // an empty `Disallow` disallows nothing
+            "disallow" if !value.is_empty() => {
+                groups.last_mut().unwrap().rules.push((false, value.into()));
+            }
+            "crawl-delay" => {
+                let delay = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|d| d.is_finite() && *d >= 0.0);
+                groups.last_mut().unwrap().crawl_delay = delay.map(Duration::from_secs_f64);
+            }
+            _ => {}
+        }
+
+        in_agents = false;
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+# comments are ignored
+User-agent: *
+Disallow: /
+Allow: /zhongwen/
+
+User-agent: Dissertation
+User-agent: OtherBot
+Disallow: /zhongwen/simp/private/
+Disallow: /*.pdf$
+Allow: /zhongwen/simp/private/ok
+Crawl-delay: 2.5
+
+Sitemap: https://www.bbc.com/sitemap.xml
+";
+
+    #[test]
+    fn uses_the_group_for_our_user_agent() {
+        let robots = Robots::parse(ROBOTS, "dissertation/0.1.0 (academic research crawler)");
+
+        assert!(robots.is_allowed("/news"));
+        assert!(robots.is_allowed("/zhongwen/simp/articles/c1"));
+        assert!(!robots.is_allowed("/zhongwen/simp/private/c1"));
+        assert!(robots.is_allowed("/zhongwen/simp/private/ok"));
+        assert!(!robots.is_allowed("/files/report.pdf"));
+        assert!(robots.is_allowed("/files/report.pdf?download=1"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_millis(2500)));
+    }
+
+    #[test]
+    fn falls_back_to_the_wildcard_group() {
+        let robots = Robots::parse(ROBOTS, "SomeBrowser/1.0");
+
+        assert!(!robots.is_allowed("/news"));
+        assert!(robots.is_allowed("/zhongwen/simp/private/c1"));
+        assert_eq!(robots.crawl_delay(), None);
+    }
+
+    #[test]
+    fn empty_disallow_allows_everything() {
+        let robots = Robots::parse("User-agent: *\nDisallow:\n", "dissertation");
+        assert!(robots.is_allowed("/anything"));
+
+        assert!(!Robots::disallow_all().is_allowed("/anything"));
+        assert!(Robots::allow_all().is_allowed("/anything"));
+    }
+}
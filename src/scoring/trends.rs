@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use color_eyre::{eyre::bail, Report, Result};
 use moka::future::Cache;
 use r2d2::Pool;
@@ -5,7 +7,7 @@ use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::named_params;
 use serde::Deserialize;
 
-use crate::rate_limiter::RateLimiters;
+use crate::http_client::{self, Clients, Endpoints, Service};
 
 use super::Region;
 
@@ -24,8 +26,14 @@ impl Default for Trends {
 }
 
 impl Trends {
+    /// Load the trends database at `./trends.db`
     pub fn new() -> Self {
-        let pool = Self::open_db();
+        Self::open("./trends.db")
+    }
+
+    /// Load the trends database at the given path, creating it if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Self {
+        let pool = Self::open_db(path.as_ref());
         let rows = Self::all_scores(&pool).unwrap();
         let cache = Cache::new(10_000_000);
 
@@ -36,8 +44,8 @@ impl Trends {
         Self { pool, cache }
     }
 
-    fn open_db() -> Pool<SqliteConnectionManager> {
-        let pool = Pool::new(SqliteConnectionManager::file("./trends.db")).unwrap();
+    fn open_db(path: &Path) -> Pool<SqliteConnectionManager> {
+        let pool = Pool::new(SqliteConnectionManager::file(path)).unwrap();
         let sql = include_str!("./create_table.sql");
         pool.get().unwrap().execute(sql, []).unwrap();
 
@@ -129,16 +137,20 @@ struct Row {
 
 /// Get the relative popularity score for a keyword from the Google Trends API
 async fn fetch_score_from_trends(word: &str, region: Region) -> Result<f64> {
-    let secret = std::env::var("GCLOUD_KEY").unwrap();
+    let secret = http_client::credential("GCLOUD_KEY");
     let region = match region {
         Region::Britain => "GB",
         Region::America => "US",
     };
-    let url = format!("https://www.googleapis.com/trends/v1beta/graph?terms={word}&key={secret}&restrictions_geo={region}");
+    let base = &Endpoints::get().trends;
+    let url = format!("{base}/graph?terms={word}&key={secret}&restrictions_geo={region}");
 
-    RateLimiters::get().wait_trends().await;
+    let request = || Clients::get().trends().get(&url);
 
-    let lines = match reqwest::get(&url).await?.json().await? {
+    let lines = match http_client::send_limited(Service::Trends, request)
+        .await?
+        .json()?
+    {
         Response::Ok { lines } => lines,
         Response::Err(e) => bail!("{e}"),
     };
@@ -164,25 +176,3 @@ struct Line {
 struct Point {
     value: f64,
 }
-
-#[cfg(test)]
-mod tests {
-    use futures::future::try_join_all;
-
-    use super::*;
-
-    #[tokio::test]
-    async fn can_google_trends_concurrent() {
-        dotenvy::dotenv().unwrap();
-
-        let trends = Trends::new();
-
-        let strings: Vec<_> = ('a'..='z')
-            .flat_map(|c1| ('a'..='z').map(move |c2| format!("{c1}{c2}")))
-            .collect();
-
-        try_join_all(strings.iter().map(|s| trends.score(s, Region::Britain)))
-            .await
-            .unwrap();
-    }
-}
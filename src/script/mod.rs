@@ -0,0 +1,157 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use clap::ValueEnum;
+
+static TABLE: OnceLock<Table> = OnceLock::new();
+
+/// The two ways of writing Chinese characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Script {
+    Simplified,
+    Traditional,
+}
+
+impl Script {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Script::Simplified => "simplified",
+            Script::Traditional => "traditional",
+        }
+    }
+
+    /// The code that Google Translate uses for Chinese written in this script
+    pub fn language_code(self) -> &'static str {
+        match self {
+            Script::Simplified => "zh-CN",
+            Script::Traditional => "zh-TW",
+        }
+    }
+
+    /// Work out which script a text is written in
+    ///
+    /// This counts the characters that are only written one way in one script (e.g. 国 and 國),
+    /// and picks whichever script has more. Returns `None` if there aren't any, such as when
+    /// every character is written the same way in both
+    pub fn detect(text: &str) -> Option<Self> {
+        let table = Table::get();
+
+        let simplified = text
+            .chars()
+            .filter(|c| table.simplified_only.contains(c))
+            .count();
+        let traditional = text
+            .chars()
+            .filter(|c| table.traditional_only.contains(c))
+            .count();
+
+        if simplified == 0 && traditional == 0 {
+            return None;
+        }
+
+        if traditional > simplified {
+            Some(Script::Traditional)
+        } else {
+            Some(Script::Simplified)
+        }
+    }
+
+    /// Convert a text into this script, one character at a time
+    ///
+    /// Characters that aren't in the conversion table are kept as they are
+    pub fn convert(self, text: &str) -> String {
+        let table = Table::get();
+        let map = match self {
+            Script::Simplified => &table.to_simplified,
+            Script::Traditional => &table.to_traditional,
+        };
+
+        text.chars().map(|c| *map.get(&c).unwrap_or(&c)).collect()
+    }
+}
+
+/// The offline conversion table, built from `characters.txt`
+struct Table {
+    to_traditional: HashMap<char, char>,
+    to_simplified: HashMap<char, char>,
+    simplified_only: HashSet<char>,
+    traditional_only: HashSet<char>,
+}
+
+impl Table {
+    fn get() -> &'static Self {
+        TABLE.get_or_init(|| Self::parse(include_str!("./characters.txt")))
+    }
+
+    fn parse(text: &str) -> Self {
+        let pairs: Vec<(char, char)> = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(str::split_whitespace)
+            .filter_map(|pair| {
+                let mut chars = pair.chars();
+                Some((chars.next()?, chars.next()?))
+            })
+            .collect();
+
+        let to_traditional: HashMap<_, _> = pairs.iter().copied().collect();
+        let to_simplified: HashMap<_, _> = pairs.iter().map(|&(s, t)| (t, s)).collect();
+
+        // a character that is the simplified form of one pair and the traditional form of another
+        // doesn't say anything about the script
+        let simplified_only = to_traditional
+            .keys()
+            .filter(|c| !to_simplified.contains_key(c))
+            .copied()
+            .collect();
+        let traditional_only = to_simplified
+            .keys()
+            .filter(|c| !to_traditional.contains_key(c))
+            .copied()
+            .collect();
+
+        Self {
+            to_traditional,
+            to_simplified,
+            simplified_only,
+            traditional_only,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIMPLIFIED: &str = "中国经济增长放缓，政府宣布新的刺激措施。";
+    const TRADITIONAL: &str = "中國經濟增長放緩，政府宣布新的刺激措施。";
+
+    #[test]
+    fn detects_script() {
+        assert_eq!(Script::detect(SIMPLIFIED), Some(Script::Simplified));
+        assert_eq!(Script::detect(TRADITIONAL), Some(Script::Traditional));
+
+        // the same in both scripts
+        assert_eq!(Script::detect("中文"), None);
+        assert_eq!(Script::detect("BBC News"), None);
+    }
+
+    #[test]
+    fn converts_between_scripts() {
+        assert_eq!(Script::Traditional.convert(SIMPLIFIED), TRADITIONAL);
+        assert_eq!(Script::Simplified.convert(TRADITIONAL), SIMPLIFIED);
+
+        // converting into the script a text is already in does nothing
+        assert_eq!(Script::Simplified.convert(SIMPLIFIED), SIMPLIFIED);
+    }
+
+    #[test]
+    fn table_is_consistent() {
+        let table = Table::get();
+
+        assert!(table.to_traditional.len() > 500);
+        assert_eq!(table.to_traditional.len(), table.to_simplified.len());
+    }
+}
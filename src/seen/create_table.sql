@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS articles (
+  -- the article's ID from `urls::article_id`, so an article is only stored once, whichever URL
+  -- it's found under
+  id TEXT PRIMARY KEY,
+  url TEXT NOT NULL,
+  -- the topic the article was first found on
+  topic TEXT NOT NULL,
+  first_seen INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS articles_first_seen ON articles (first_seen);
+
+-- settings that change how the store is read, which have to be the same on every run
+CREATE TABLE IF NOT EXISTS meta (
+  name TEXT PRIMARY KEY,
+  value TEXT NOT NULL
+);
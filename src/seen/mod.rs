@@ -0,0 +1,206 @@
+use std::{collections::HashSet, path::Path};
+
+use color_eyre::{eyre::bail, Result};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use reqwest::Url;
+use rusqlite::named_params;
+
+use crate::{audit::unix_millis, urls};
+
+/// A store of every article found by earlier scrapes, so a scrape can pick out the new ones
+///
+/// Each article is stored once, by its ID from [`urls::article_id`], along with the URL and topic
+/// it was first found under and when it was first seen. Running the same scrape every week grows
+/// the store, with each run only adding what has been published since
+pub struct SeenStore {
+    pool: Pool<SqliteConnectionManager>,
+    collapse_scripts: bool,
+}
+
+/// An article in the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeenArticle {
+    pub url: String,
+    pub topic: String,
+    /// When the article was first found, in milliseconds since the Unix epoch
+    pub first_seen: i64,
+}
+
+impl SeenStore {
+    /// Open the store at the given path, creating it if it doesn't exist
+    ///
+    /// `collapse_scripts` is passed to [`urls::article_id`], so has to be the same on every run.
+    /// It is saved when the store is created, and opening the store with a different value fails
+    pub fn open(path: &Path, collapse_scripts: bool) -> Result<Self> {
+        let pool = Pool::new(SqliteConnectionManager::file(path))?;
+        let sql = include_str!("./create_table.sql");
+        let conn = pool.get()?;
+        conn.execute_batch(sql)?;
+
+        conn.execute(
+            "INSERT OR IGNORE INTO meta (name, value) VALUES ('collapse_scripts', ?1)",
+            [collapse_scripts.to_string()],
+        )?;
+        let saved: String = conn.query_row(
+            "SELECT value FROM meta WHERE name = 'collapse_scripts'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if saved != collapse_scripts.to_string() {
+            let flag = if saved == "true" { "with" } else { "without" };
+            bail!(
+                "{} was created {flag} --collapse-scripts, so it must be used {flag} it",
+                path.display()
+            );
+        }
+
+        Ok(Self {
+            pool,
+            collapse_scripts,
+        })
+    }
+
+    /// The IDs of every article in the store
+    pub async fn ids(&self) -> Result<HashSet<String>> {
+        let conn = self.pool.get()?;
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let mut statement = conn.prepare("SELECT id FROM articles")?;
+            let ids = statement
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            Ok(ids)
+        })
+        .await?
+    }
+
+    /// Add articles found on the given topics, skipping any that are already in the store
+    ///
+    /// Returns the articles that weren't already in the store
+    pub async fn add(&self, articles: Vec<(Url, String)>) -> Result<Vec<Url>> {
+        let mut conn = self.pool.get()?;
+        let articles: Vec<_> = articles
+            .into_iter()
+            .map(|(url, topic)| (urls::article_id(&url, self.collapse_scripts), url, topic))
+            .collect();
+        let first_seen = unix_millis();
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            // a single transaction, rather than one for each article
+            let transaction = conn.transaction()?;
+            let mut added = Vec::new();
+
+            {
+                let sql = "INSERT OR IGNORE INTO articles (id, url, topic, first_seen) \
+                    VALUES (:id, :url, :topic, :first_seen)";
+                let mut statement = transaction.prepare_cached(sql)?;
+
+                for (id, url, topic) in articles {
+                    let inserted = statement.execute(named_params! {
+                        ":id": id,
+                        ":url": url.as_str(),
+                        ":topic": topic,
+                        ":first_seen": first_seen,
+                    })?;
+
+                    if inserted > 0 {
+                        added.push(url);
+                    }
+                }
+            }
+
+            transaction.commit()?;
+            Ok(added)
+        })
+        .await?
+    }
+
+    /// Every article in the store, in the order they were first seen
+    pub async fn all(&self) -> Result<Vec<SeenArticle>> {
+        let conn = self.pool.get()?;
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let sql = "SELECT url, topic, first_seen FROM articles ORDER BY first_seen, rowid";
+            let mut statement = conn.prepare(sql)?;
+            let articles = statement
+                .query_map([], |row| {
+                    Ok(SeenArticle {
+                        url: row.get("url")?,
+                        topic: row.get("topic")?,
+                        first_seen: row.get("first_seen")?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(articles)
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(path: &str) -> Url {
+        Url::parse(&format!("https://www.bbc.com/zhongwen/{path}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn only_adds_new_articles() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SeenStore::open(&dir.path().join("seen.db"), true).unwrap();
+        let topic = |name: &str| name.to_string();
+
+        let added = store
+            .add(vec![
+                (url("simp/a"), topic("economy")),
+                (url("simp/b"), topic("economy")),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(added, [url("simp/a"), url("simp/b")]);
+
+        // the traditional version of an article is the same article
+        let added = store
+            .add(vec![
+                (url("trad/a"), topic("science")),
+                (url("simp/c"), topic("science")),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(added, [url("simp/c")]);
+
+        let id = urls::article_id(&url("trad/b"), true);
+        assert!(store.ids().await.unwrap().contains(&id));
+
+        let all: Vec<_> = store
+            .all()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|article| (article.url, article.topic))
+            .collect();
+        assert_eq!(
+            all,
+            [
+                (url("simp/a").into(), topic("economy")),
+                (url("simp/b").into(), topic("economy")),
+                (url("simp/c").into(), topic("science")),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_collapse_scripts_the_same() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen.db");
+
+        SeenStore::open(&path, true).unwrap();
+        assert!(SeenStore::open(&path, true).is_ok());
+
+        let e = SeenStore::open(&path, false).err().unwrap();
+        assert!(e.to_string().contains("created with --collapse-scripts"));
+    }
+}
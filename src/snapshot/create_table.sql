@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS pages (
+  hash TEXT PRIMARY KEY,
+  body BLOB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS snapshots (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  url TEXT NOT NULL,
+  fetched_at INTEGER NOT NULL,
+  status INTEGER NOT NULL,
+  headers TEXT NOT NULL,
+  hash TEXT NOT NULL REFERENCES pages (hash)
+);
+
+CREATE INDEX IF NOT EXISTS snapshots_url_fetched_at ON snapshots (url, fetched_at);
+
+-- every version of each page's description, added whenever it differs from the one before
+CREATE TABLE IF NOT EXISTS descriptions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  url TEXT NOT NULL,
+  description TEXT NOT NULL,
+  first_seen INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS descriptions_url ON descriptions (url, id);
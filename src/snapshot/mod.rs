@@ -0,0 +1,357 @@
+use std::{
+    io::{Read, Write},
+    path::Path,
+    sync::OnceLock,
+};
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use rusqlite::{named_params, OpenFlags, OptionalExtension, TransactionBehavior};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audit::unix_millis,
+    cassette::{headers_from_json, headers_to_json},
+    http_client::HttpResponse,
+};
+
+static SNAPSHOTS: OnceLock<SnapshotStore> = OnceLock::new();
+
+/// An archive of every page fetched with [`crate::html::load_html`]
+///
+/// Each page is stored with its URL, the time it was fetched and the response's status and
+/// headers. Bodies are compressed, and stored once per distinct body (keyed by their SHA-256
+/// hash), so fetching an unchanged page again only adds a small row.
+///
+/// In offline mode, pages are served from the archive instead of the network, so extraction can
+/// be re-run on exactly the pages seen in an earlier run.
+///
+/// The archive also keeps the history of each page's description, since descriptions are often
+/// edited after an article is published
+pub struct SnapshotStore {
+    pool: Pool<SqliteConnectionManager>,
+    offline: bool,
+}
+
+/// A page as it was when it was fetched
+pub(crate) struct Snapshot {
+    /// When the page was fetched, in milliseconds since the Unix epoch
+    pub fetched_at: i64,
+    pub response: HttpResponse,
+}
+
+impl Snapshot {
+    /// The headers that ask the server to only send the page again if it has changed since this
+    /// snapshot, from its `ETag` and `Last-Modified` headers
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(etag) = self.response.headers.get(ETAG) {
+            headers.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = self.response.headers.get(LAST_MODIFIED) {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+
+        headers
+    }
+}
+
+/// Which version of a page's description was seen, from [`SnapshotStore::record_description`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptionVersion {
+    /// The number of the version, counting from 1 for the first description seen for the page
+    pub version: usize,
+    /// The version before, if this description is different from the one seen last time
+    pub previous: Option<String>,
+}
+
+impl SnapshotStore {
+    /// Start saving pages to the archive at the given path, or if `offline` is set, start
+    /// serving pages from it
+    pub fn enable(path: &Path, offline: bool) -> Result<&'static Self> {
+        let manager = if offline {
+            if !path.exists() {
+                bail!("no snapshot archive found at {}", path.display());
+            }
+
+            SqliteConnectionManager::file(path).with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY)
+        } else {
+            SqliteConnectionManager::file(path)
+        };
+
+        let pool = Pool::new(manager)?;
+
+        if !offline {
+            let sql = include_str!("./create_table.sql");
+            pool.get()?.execute_batch(sql)?;
+        }
+
+        SNAPSHOTS
+            .set(Self { pool, offline })
+            .map_err(|_| eyre!("a snapshot archive has already been enabled"))?;
+
+        Ok(SNAPSHOTS.get().unwrap())
+    }
+
+    /// Get a handle to the global snapshot archive, if one has been enabled
+    pub fn get() -> Option<&'static Self> {
+        SNAPSHOTS.get()
+    }
+
+    /// Whether pages are currently being served from the archive rather than the network
+    pub fn is_offline() -> bool {
+        Self::get().is_some_and(|store| store.offline)
+    }
+
+    /// Save a fetched page
+    pub(crate) async fn save(&self, url: &str, response: &HttpResponse) -> Result<()> {
+        let conn = self.pool.get()?;
+        let url = url.to_string();
+        let status = response.status.as_u16();
+        let headers = headers_to_json(&response.headers);
+        let hash = hash(&response.body);
+        let body = compress(&response.body)?;
+        let fetched_at = unix_millis();
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let sql = "INSERT OR IGNORE INTO pages (hash, body) VALUES (:hash, :body)";
+            let mut statement = conn.prepare_cached(sql)?;
+            statement.execute(named_params! { ":hash": hash, ":body": body })?;
+
+            let sql = "INSERT INTO snapshots (url, fetched_at, status, headers, hash) \
+                VALUES (:url, :fetched_at, :status, :headers, :hash)";
+            let mut statement = conn.prepare_cached(sql)?;
+            statement.execute(named_params! {
+                ":url": url,
+                ":fetched_at": fetched_at,
+                ":status": status,
+                ":headers": headers,
+                ":hash": hash,
+            })?;
+
+            Ok(())
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Record the description a page has now, adding it to the page's history if it's different
+    /// from the last one recorded
+    pub async fn record_description(
+        &self,
+        url: &str,
+        description: &str,
+    ) -> Result<DescriptionVersion> {
+        let mut conn = self.pool.get()?;
+        let url = url.to_string();
+        let description = description.to_string();
+        let first_seen = unix_millis();
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            // the history is read and added to in one transaction, which takes the write lock
+            // straight away, so two fetches of the same page can't both add the same version
+            let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+            let sql = "SELECT description FROM descriptions WHERE url = (?1) ORDER BY id";
+            let history: Vec<String> = transaction
+                .prepare_cached(sql)?
+                .query_map([&url], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+
+            let latest = history.last();
+            if latest == Some(&description) {
+                return Ok(DescriptionVersion {
+                    version: history.len(),
+                    previous: None,
+                });
+            }
+
+            let sql = "INSERT INTO descriptions (url, description, first_seen) \
+                VALUES (:url, :description, :first_seen)";
+            transaction.prepare_cached(sql)?.execute(named_params! {
+                ":url": url,
+                ":description": description,
+                ":first_seen": first_seen,
+            })?;
+            transaction.commit()?;
+
+            Ok(DescriptionVersion {
+                version: history.len() + 1,
+                previous: latest.cloned(),
+            })
+        })
+        .await?
+    }
+
+    /// Load the most recent snapshot of a page, if there is one
+    pub(crate) async fn latest(&self, url: &str) -> Result<Option<Snapshot>> {
+        let conn = self.pool.get()?;
+        let url = url.to_string();
+
+        let row = tokio::task::spawn_blocking(move || -> Result<_> {
+            let sql = "SELECT snapshots.fetched_at, snapshots.status, snapshots.headers, \
+                pages.body FROM snapshots JOIN pages ON pages.hash = snapshots.hash \
+                WHERE snapshots.url = (?1) ORDER BY snapshots.fetched_at DESC, snapshots.id DESC \
+                LIMIT 1";
+            let mut statement = conn.prepare_cached(sql)?;
+            let row = statement
+                .query_row([&url], |row| {
+                    Ok((
+                        row.get::<_, i64>("fetched_at")?,
+                        row.get::<_, u16>("status")?,
+                        row.get::<_, String>("headers")?,
+                        row.get::<_, Vec<u8>>("body")?,
+                    ))
+                })
+                .optional()?;
+
+            Ok(row)
+        })
+        .await??;
+
+        let Some((fetched_at, status, headers, body)) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some(Snapshot {
+            fetched_at,
+            response: HttpResponse {
+                status: StatusCode::from_u16(status)?,
+                headers: headers_from_json(&headers)?,
+                body: decompress(&body)?,
+            },
+        }))
+    }
+}
+
+/// The hex encoded SHA-256 hash of a page's body
+fn hash(body: &[u8]) -> String {
+    Sha256::digest(body)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub(crate) fn compress(body: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    Ok(encoder.finish()?)
+}
+
+pub(crate) fn decompress(body: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(body).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    /// Open an archive for saving pages to, without making it the global one
+    fn open(path: &Path) -> SnapshotStore {
+        let pool = Pool::new(SqliteConnectionManager::file(path)).unwrap();
+        pool.get()
+            .unwrap()
+            .execute_batch(include_str!("./create_table.sql"))
+            .unwrap();
+
+        SnapshotStore {
+            pool,
+            offline: false,
+        }
+    }
+
+    fn response(status: u16, body: &str) -> HttpResponse {
+        HttpResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            headers: HeaderMap::new(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_pages_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(&dir.path().join("snapshots.db"));
+
+        let url = "https://www.bbc.com/zhongwen/simp/articles/c1";
+        store.save(url, &response(200, "<p>旧</p>")).await.unwrap();
+        store.save(url, &response(200, "<p>新</p>")).await.unwrap();
+        store.save(url, &response(200, "<p>新</p>")).await.unwrap();
+
+        let latest = store.latest(url).await.unwrap().unwrap();
+        assert_eq!(latest.response.status, StatusCode::OK);
+        assert_eq!(latest.response.text(), "<p>新</p>");
+
+        // the same body is only stored once
+        let conn = store.pool.get().unwrap();
+        let count = |table: &str| -> i64 {
+            let sql = format!("SELECT COUNT(*) FROM {table}");
+            conn.query_row(&sql, [], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(count("snapshots"), 3);
+        assert_eq!(count("pages"), 2);
+
+        assert!(store.latest("https://example.com").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_description_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(&dir.path().join("snapshots.db"));
+        let url = "https://www.bbc.com/zhongwen/simp/articles/c1";
+
+        let version = |version, previous: Option<&str>| DescriptionVersion {
+            version,
+            previous: previous.map(String::from),
+        };
+
+        let recorded = store.record_description(url, "旧").await.unwrap();
+        assert_eq!(recorded, version(1, None));
+
+        // seeing the same description again isn't a new version
+        let recorded = store.record_description(url, "旧").await.unwrap();
+        assert_eq!(recorded, version(1, None));
+
+        let recorded = store.record_description(url, "新").await.unwrap();
+        assert_eq!(recorded, version(2, Some("旧")));
+
+        // changing back is a new version too
+        let recorded = store.record_description(url, "旧").await.unwrap();
+        assert_eq!(recorded, version(3, Some("新")));
+    }
+
+    #[test]
+    fn asks_for_changes_since_the_snapshot() {
+        let mut response = response(200, "<p>旧</p>");
+        response
+            .headers
+            .insert(ETAG, HeaderValue::from_static("\"abc\""));
+        response.headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Mon, 07 Aug 2023 09:00:00 GMT"),
+        );
+        let snapshot = Snapshot {
+            fetched_at: 0,
+            response,
+        };
+
+        let headers = snapshot.conditional_headers();
+        assert_eq!(headers[IF_NONE_MATCH], "\"abc\"");
+        assert_eq!(headers[IF_MODIFIED_SINCE], "Mon, 07 Aug 2023 09:00:00 GMT");
+    }
+}
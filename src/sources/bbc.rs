@@ -0,0 +1,42 @@
+use reqwest::Url;
+
+use super::{on_domain, NewsSource, Outlet};
+use crate::{html::SelectorProfile, http_client::Service};
+
+/// BBC News Chinese (`bbc.com/zhongwen`), in simplified and traditional Chinese
+///
+/// Its built-in selectors are [`SelectorProfile::default`]
+pub struct BbcChinese {
+    profile: SelectorProfile,
+}
+
+impl BbcChinese {
+    pub fn new(profile: SelectorProfile) -> Self {
+        Self { profile }
+    }
+}
+
+impl Default for BbcChinese {
+    fn default() -> Self {
+        Self::new(SelectorProfile::default())
+    }
+}
+
+impl NewsSource for BbcChinese {
+    fn outlet(&self) -> Outlet {
+        Outlet::BbcChinese
+    }
+
+    fn service(&self) -> Service {
+        Service::Bbc
+    }
+
+    fn profile(&self) -> &SelectorProfile {
+        &self.profile
+    }
+
+    fn owns(&self, url: &Url) -> bool {
+        // BBC's feeds are served from `feeds.bbci.co.uk`
+        on_domain(url, &["bbc.com", "bbc.co.uk", "bbci.co.uk"])
+    }
+}
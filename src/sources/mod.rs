@@ -0,0 +1,254 @@
+use std::str::FromStr;
+
+use color_eyre::{eyre::bail, Report, Result};
+use futures::future::BoxFuture;
+use reqwest::Url;
+
+use crate::{
+    html::{self, Article, SelectorProfile},
+    http_client::Service,
+    urls,
+};
+
+mod bbc;
+mod voa;
+
+pub use bbc::BbcChinese;
+pub use voa::VoaChinese;
+
+/// A news outlet that articles can be scraped from
+///
+/// Each outlet has its own selectors, and its pages are fetched under its own rate limiter, so
+/// scraping one outlet never slows down another. The provided methods find, fetch and read
+/// articles the usual way, and only need overriding for a site that works differently
+pub trait NewsSource: Send + Sync {
+    fn outlet(&self) -> Outlet;
+
+    /// The service that the outlet's pages are fetched as, which picks their rate limiter
+    fn service(&self) -> Service;
+
+    /// The selectors for the outlet's pages
+    fn profile(&self) -> &SelectorProfile;
+
+    /// Whether a URL is on one of the outlet's sites
+    fn owns(&self, url: &Url) -> bool;
+
+    /// The URL of a page of a topic, counting from 1
+    fn page_url(&self, topic_url: &str, page: usize) -> String {
+        with_query_pair(topic_url, "page", &page.to_string())
+    }
+
+    /// Load the HTML of one of the outlet's pages
+    fn load_html<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(html::load_html(url, self.service()))
+    }
+
+    /// Return the article links on a topic page, resolved against the page's URL and normalised
+    fn article_links(&self, page_url: &str, page: &str) -> Result<Vec<Url>> {
+        let base = Url::parse(page_url)?;
+        let links = html::article_links(page, self.profile())?;
+
+        Ok(links
+            .iter()
+            .filter_map(|href| urls::canonicalise(&base, href))
+            .collect())
+    }
+
+    /// The number of pages in a topic, read from its first page
+    ///
+    /// Returns `None` if the page doesn't say
+    fn page_count(&self, page: &str) -> Result<Option<usize>> {
+        html::page_count(page, self.profile())
+    }
+
+    /// Whether a URL (such as one listed in a sitemap or feed) is one of the outlet's articles
+    fn is_article(&self, url: &Url) -> bool {
+        self.profile().is_article_link(url.as_str())
+    }
+
+    /// Read an article's metadata and body from its HTML
+    fn extract_article(&self, page: &str) -> Result<Article> {
+        html::extract_article(page, self.profile())
+    }
+
+    /// Load an article, and return its metadata and body
+    fn article<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Article>> {
+        Box::pin(async move {
+            let page = self.load_html(url).await?;
+            self.extract_article(&page)
+        })
+    }
+
+    /// Load an article, and return its description
+    fn description<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let article = self.article(url).await?;
+            let Some(description) = article.metadata.description else {
+                bail!("page had no description tag");
+            };
+
+            Ok(description)
+        })
+    }
+}
+
+/// The outlets there is a [`NewsSource`] for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outlet {
+    BbcChinese,
+    VoaChinese,
+}
+
+impl Outlet {
+    pub const ALL: [Outlet; 2] = [Outlet::BbcChinese, Outlet::VoaChinese];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Outlet::BbcChinese => "bbc",
+            Outlet::VoaChinese => "voa",
+        }
+    }
+
+    /// Make the source for this outlet, using the given selectors instead of its built-in ones
+    pub fn source(self, profile: Option<SelectorProfile>) -> Box<dyn NewsSource> {
+        match self {
+            Outlet::BbcChinese => Box::new(BbcChinese::new(profile.unwrap_or_default())),
+            Outlet::VoaChinese => {
+                Box::new(VoaChinese::new(profile.unwrap_or_else(VoaChinese::profile)))
+            }
+        }
+    }
+}
+
+impl FromStr for Outlet {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Outlet::ALL.into_iter().find(|outlet| outlet.to_str() == s) {
+            Some(outlet) => Ok(outlet),
+            None => bail!("unknown source `{s}`, expected `bbc` or `voa`"),
+        }
+    }
+}
+
+/// A source for every outlet, for finding the outlet that a URL belongs to
+pub struct Sources {
+    /// The source for URLs that aren't on any outlet's site
+    fallback: Box<dyn NewsSource>,
+    others: Vec<Box<dyn NewsSource>>,
+}
+
+impl Sources {
+    /// Make a source for every outlet, where `fallback` is used for URLs that aren't on any of
+    /// their sites, and uses the given selectors instead of its built-in ones
+    pub fn new(fallback: Outlet, profile: Option<SelectorProfile>) -> Self {
+        let others = Outlet::ALL
+            .into_iter()
+            .filter(|outlet| *outlet != fallback)
+            .map(|outlet| outlet.source(None))
+            .collect();
+
+        Self {
+            fallback: fallback.source(profile),
+            others,
+        }
+    }
+
+    /// The source for the outlet whose site a URL is on
+    pub fn for_url(&self, url: &str) -> &dyn NewsSource {
+        let Ok(url) = Url::parse(url) else {
+            return self.fallback.as_ref();
+        };
+
+        std::iter::once(&self.fallback)
+            .chain(&self.others)
+            .find(|source| source.owns(&url))
+            .unwrap_or(&self.fallback)
+            .as_ref()
+    }
+}
+
+/// Whether a URL's host is one of the given domains, or a subdomain of one
+fn on_domain(url: &Url, domains: &[&str]) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+
+    domains.iter().any(|domain| {
+        host == *domain
+            || host
+                .strip_suffix(domain)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Add a parameter to the end of a URL's query, keeping any parameters it already has
+///
+/// A URL that can't be parsed (which will fail to load anyway) has the parameter tacked on to the
+/// end as it is
+pub(crate) fn with_query_pair(url: &str, name: &str, value: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        return format!("{url}{separator}{name}={value}");
+    };
+
+    parsed.query_pairs_mut().append_pair(name, value);
+    parsed.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_page_numbers_to_the_query() {
+        let bbc = BbcChinese::default();
+
+        assert_eq!(
+            bbc.page_url("https://www.bbc.com/zhongwen/simp/topics/c1", 2),
+            "https://www.bbc.com/zhongwen/simp/topics/c1?page=2"
+        );
+        assert_eq!(
+            bbc.page_url("https://www.bbc.com/zhongwen/simp/topics/c1?lang=zh", 2),
+            "https://www.bbc.com/zhongwen/simp/topics/c1?lang=zh&page=2"
+        );
+    }
+
+    #[test]
+    fn picks_source_by_domain() {
+        let sources = Sources::new(Outlet::BbcChinese, None);
+        let outlet = |url| sources.for_url(url).outlet();
+
+        assert_eq!(
+            outlet("https://www.voachinese.com/a/story/7212345.html"),
+            Outlet::VoaChinese
+        );
+        assert_eq!(
+            outlet("https://www.bbc.com/zhongwen/simp/articles/c1"),
+            Outlet::BbcChinese
+        );
+        assert_eq!(
+            outlet("http://127.0.0.1:8080/a/story/1.html"),
+            Outlet::BbcChinese
+        );
+        assert_eq!(
+            outlet("https://notvoachinese.com/a/story/1.html"),
+            Outlet::BbcChinese
+        );
+
+        let sources = Sources::new(Outlet::VoaChinese, None);
+        assert_eq!(
+            sources.for_url("http://127.0.0.1:8080/").outlet(),
+            Outlet::VoaChinese
+        );
+    }
+
+    #[test]
+    fn parses_outlet_names() {
+        for outlet in Outlet::ALL {
+            assert_eq!(outlet.to_str().parse::<Outlet>().unwrap(), outlet);
+        }
+
+        assert!("cnn".parse::<Outlet>().is_err());
+    }
+}
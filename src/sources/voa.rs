@@ -0,0 +1,173 @@
+use reqwest::Url;
+
+use super::{on_domain, with_query_pair, NewsSource, Outlet};
+use crate::{
+    html::{LinkPattern, SelectorProfile},
+    http_client::Service,
+};
+
+/// Voice of America Chinese (`voachinese.com`), in simplified Chinese
+pub struct VoaChinese {
+    profile: SelectorProfile,
+}
+
+impl VoaChinese {
+    pub fn new(profile: SelectorProfile) -> Self {
+        Self { profile }
+    }
+
+    /// The built-in selectors for VOA Chinese
+    ///
+    /// Article URLs look like `/a/<slug>/<id>.html`, and the body is in the `.wsw` ("wysiwyg")
+    /// block that all of VOA's sites use. Section pages have no numbered pagination control,
+    /// just a "load more" link, so pages are followed until one has no new articles
+    pub fn profile() -> SelectorProfile {
+        let selectors = |list: &[&str]| list.iter().map(|s| s.parse().unwrap()).collect();
+
+        SelectorProfile {
+            links: vec!["main a[href]".into(), "a[href]".into()],
+            link_patterns: vec![LinkPattern::new(r"/a/[^/?#]+/\d+\.html(?:$|[?#])")],
+            pagination: vec![],
+            title: selectors(&[r#"meta[property="og:title"]@content"#, "h1", "title"]),
+            date: selectors(&[
+                "time[datetime]@datetime",
+                r#"meta[property="article:published_time"]@content"#,
+            ]),
+            content: vec![
+                ".wsw".into(),
+                "#article-content".into(),
+                "article".into(),
+                "main".into(),
+            ],
+            boilerplate: [
+                "nav",
+                "footer",
+                "aside",
+                "form",
+                "script",
+                "style",
+                "noscript",
+                r#"[role="banner"]"#,
+                // embedded videos, galleries and "related" boxes inside the body
+                ".media-block-wrap",
+                ".wsw__embed",
+            ]
+            .map(String::from)
+            .into(),
+            section: selectors(&[r#"meta[property="article:section"]@content"#, ".category a"]),
+            author: selectors(&[r#"meta[name="Author"]@content"#, ".links__item-link"]),
+            ..SelectorProfile::default()
+        }
+    }
+}
+
+impl Default for VoaChinese {
+    fn default() -> Self {
+        Self::new(Self::profile())
+    }
+}
+
+impl NewsSource for VoaChinese {
+    fn outlet(&self) -> Outlet {
+        Outlet::VoaChinese
+    }
+
+    fn service(&self) -> Service {
+        Service::Voa
+    }
+
+    fn profile(&self) -> &SelectorProfile {
+        &self.profile
+    }
+
+    fn owns(&self, url: &Url) -> bool {
+        on_domain(url, &["voachinese.com"])
+    }
+
+    /// VOA numbers the pages of a section from 0, in the `p` query parameter
+    fn page_url(&self, topic_url: &str, page: usize) -> String {
+        with_query_pair(topic_url, "p", &page.saturating_sub(1).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::BlockKind;
+
+    const SECTION: &str = r#"<html><body>
+        <nav><a href="/z/1739">中国</a></nav>
+        <main>
+          <a href="/a/china-economy/7212345.html">中国经济</a>
+          <a href="https://www.voachinese.com/a/us-china-talks/7212346.html?withmediaplayer=1">中美会谈</a>
+          <a href="/z/1739?p=1">更多</a>
+        </main>
+    </body></html>"#;
+
+    const ARTICLE: &str = r#"<html><head>
+        <meta property="og:title" content="中国经济增长放缓">
+        <meta name="description" content="中国经济增长放缓，政府宣布新的刺激措施。">
+    </head><body>
+        <h1>中国经济增长放缓</h1>
+        <time datetime="2023-08-07T10:00:00+08:00">2023年8月7日</time>
+        <div id="article-content"><div class="wsw">
+          <p>中国政府周一宣布了一系列新的经济刺激措施。</p>
+          <div class="media-block-wrap"><p>相关新闻</p></div>
+          <p>分析人士说，这些措施可能不够。</p>
+        </div></div>
+    </body></html>"#;
+
+    #[test]
+    fn finds_voa_articles() {
+        let voa = VoaChinese::default();
+        let links = voa
+            .article_links("https://www.voachinese.com/z/1739", SECTION)
+            .unwrap();
+
+        let links: Vec<_> = links.into_iter().map(String::from).collect();
+        assert_eq!(
+            links,
+            [
+                "https://www.voachinese.com/a/china-economy/7212345.html",
+                "https://www.voachinese.com/a/us-china-talks/7212346.html?withmediaplayer=1",
+            ]
+        );
+        assert_eq!(voa.page_count(SECTION).unwrap(), None);
+        assert_eq!(
+            voa.page_url("https://www.voachinese.com/z/1739", 2),
+            "https://www.voachinese.com/z/1739?p=1"
+        );
+        assert_eq!(
+            voa.page_url("https://www.voachinese.com/z/1739?utm=x", 3),
+            "https://www.voachinese.com/z/1739?utm=x&p=2"
+        );
+    }
+
+    #[test]
+    fn reads_voa_articles() {
+        let article = VoaChinese::default().extract_article(ARTICLE).unwrap();
+
+        assert_eq!(
+            article.metadata.headline.as_deref(),
+            Some("中国经济增长放缓")
+        );
+        assert_eq!(
+            article.metadata.published.as_deref(),
+            Some("2023-08-07T10:00:00+08:00")
+        );
+
+        let paragraphs: Vec<_> = article
+            .content
+            .iter()
+            .filter(|block| block.kind == BlockKind::Paragraph)
+            .map(|block| block.text.as_str())
+            .collect();
+        assert_eq!(
+            paragraphs,
+            [
+                "中国政府周一宣布了一系列新的经济刺激措施。",
+                "分析人士说，这些措施可能不够。",
+            ]
+        );
+    }
+}
@@ -1,18 +1,21 @@
-use color_eyre::Report;
+use color_eyre::{eyre::bail, Report};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{rate_limiter::RateLimiters, http_client::Clients};
+use crate::http_client::{self, Clients, Endpoints, Service};
+
+/// The number of times a prompt is sent before giving up, while chatgpt keeps responding with a
+/// server error (or an error without a code)
+const MAX_ATTEMPTS: usize = 3;
 
 /// Ask chatgpt the given prompt
 ///
 /// This behaves similarly to:
 ///  - opening a brand new chat conversation with chatgpt
 ///  - asking the given prompt
-///  (i.e. each message is treated as a fresh conversation)
+///    (i.e. each message is treated as a fresh conversation)
 pub async fn ask_chatgpt(prompt: &str) -> Result<String, Report> {
-    let client = &Clients::get().chatgpt;
-    RateLimiters::get().wait_chatgpt().await;
+    let client = Clients::get().chatgpt();
 
     let body = json!({
         "model": "gpt-3.5-turbo",
@@ -22,21 +25,28 @@ pub async fn ask_chatgpt(prompt: &str) -> Result<String, Report> {
         "temperature": 0,
     });
 
+    let url = format!("{}/chat/completions", Endpoints::get().chatgpt);
+
+    let mut attempts = 0;
+
     let choices = loop {
-        let response = client
-            .post("https://api.openai.com/v1/chat/completions")
-            .json(&body)
-            .send()
+        attempts += 1;
+
+        let request = || client.post(&url).json(&body);
+        let response = http_client::send_limited(Service::Chatgpt, request)
             .await?
-            .json()
-            .await?;
+            .json()?;
 
         match response {
             Response::Ok { choices } => break choices,
             Response::Err { code, content } => match code {
                 // if there is no error code, or is it 5XX, retry
-                None | Some(500..=599) => continue,
-                Some(x) => panic!("code {x}, error: {content}"),
+                None | Some(500..=599) if attempts < MAX_ATTEMPTS => {
+                    tracing::warn!("chatgpt responded with an error, retrying: {content}");
+                    continue;
+                }
+                None => bail!("chatgpt responded with an error: {content}"),
+                Some(code) => bail!("chatgpt responded with error code {code}: {content}"),
             },
         };
     };
@@ -2,29 +2,29 @@ use color_eyre::Report;
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::http_client::Clients;
-
-
-const URL: &str = "https://translate.googleapis.com/v3beta1";
-
-/// Translate the given string from Chinese to English using the Google Cloud Translate API
-pub async fn google_translate(s: &str) -> Result<String, Report> {
-    let project_id = std::env::var("GCLOUD_PROJECT_ID").unwrap();
-    let url = format!("{URL}/projects/{project_id}:translateText");
-
-    let client = &Clients::get().google_translate;
-    let Response { translations } = client
-        .post(url)
-        .json(&json!({
-            "contents": [ s ],
-            "sourceLanguageCode": "zh-CN",
-            "targetLanguageCode": "en-US",
-
-        }))
-        .send()
+use crate::http_client::{self, Clients, Endpoints, Service};
+
+/// Translate the given string into English using the Google Cloud Translate API
+///
+/// `source_language` is the code of the language the string is written in, e.g. `zh-TW` for
+/// traditional Chinese or `ru` for Russian
+pub async fn google_translate(s: &str, source_language: &str) -> Result<String, Report> {
+    let project_id = http_client::credential("GCLOUD_PROJECT_ID");
+    let base = &Endpoints::get().google_translate;
+    let url = format!("{base}/projects/{project_id}:translateText");
+
+    let client = Clients::get().google_translate();
+    let body = json!({
+        "contents": [ s ],
+        "sourceLanguageCode": source_language,
+        "targetLanguageCode": "en-US",
+
+    });
+
+    let request = || client.post(&url).json(&body);
+    let Response { translations } = http_client::send_limited(Service::GoogleTranslate, request)
         .await?
-        .json()
-        .await?;
+        .json()?;
 
     Ok(translations[0].translated_text.to_string())
 }
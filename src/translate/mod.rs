@@ -7,23 +7,38 @@ use color_eyre::Result;
 
 use crate::{
     input::{ChatgptPrompts, Prompt},
+    language::Language,
     scoring::Region,
+    script::Script,
 };
 
-/// Generate all translations of the input text
-pub async fn translate(chinese_text: &str, prompts: &ChatgptPrompts) -> Result<Translations> {
-    let google = google_translate::google_translate(chinese_text).await?;
+/// Generate all translations of the input text, which is written in the given language (and, for
+/// Chinese, the given script)
+///
+/// In each prompt, `{text}` is replaced with the text, and `{language}` with the name of its
+/// language. `{chinese}` is kept as another name for `{text}`, for older prompt files
+pub async fn translate(
+    source_text: &str,
+    language: Language,
+    script: Option<Script>,
+    prompts: &ChatgptPrompts,
+) -> Result<Translations> {
+    let source_language = language.google_code(script);
+    let google = google_translate::google_translate(source_text, source_language).await?;
 
     let mut chatgpt = HashMap::new();
 
     for (name, Prompt { text, region }) in prompts.iter() {
-        let prompt = text.replace("{chinese}", chinese_text);
+        let prompt = text
+            .replace("{language}", language.name())
+            .replace("{text}", source_text)
+            .replace("{chinese}", source_text);
         let translated = chatgpt::ask_chatgpt(&prompt).await?;
         chatgpt.insert(name.to_string(), (translated, *region));
     }
 
     Ok(Translations {
-        chinese_text: chinese_text.into(),
+        source_text: source_text.into(),
         google,
         chatgpt,
     })
@@ -31,7 +46,7 @@ pub async fn translate(chinese_text: &str, prompts: &ChatgptPrompts) -> Result<T
 
 #[derive(Debug)]
 pub struct Translations {
-    pub chinese_text: String,
+    pub source_text: String,
     pub google: String,
     pub chatgpt: HashMap<String, (String, Region)>,
 }
@@ -0,0 +1,120 @@
+use reqwest::Url;
+
+/// Query parameters that only track where a visitor came from, and never change the page
+///
+/// Parameters starting with any of [`TRACKING_PREFIXES`] are also removed
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "ocid", "xtor", "mc_cid", "mc_eid"];
+
+/// Prefixes of tracking query parameters (e.g. BBC's `at_medium`, `at_campaign`, etc.)
+const TRACKING_PREFIXES: &[&str] = &["utm_", "at_", "ns_"];
+
+/// Path segments that pick the simplified or traditional Chinese version of a page
+const SCRIPT_SEGMENTS: &[&str] = &["simp", "trad"];
+
+/// Resolve a link against the URL of the page it was found on, and normalise it
+///
+/// Returns `None` if the link can't be parsed, or isn't an `http` or `https` URL
+pub fn canonicalise(base: &Url, href: &str) -> Option<Url> {
+    let url = base.join(href.trim()).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| normalise(url))
+}
+
+/// Normalise a URL, so that different ways of writing the same page's URL are the same
+///
+/// The scheme and host are lowercased, default ports and fragments are removed, tracking query
+/// parameters are removed and the rest are sorted, and any trailing slash is removed from the
+/// path
+pub fn normalise(mut url: Url) -> Url {
+    // `Url` already lowercases the scheme and host, and drops default ports
+    url.set_fragment(None);
+
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    query.sort();
+
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+
+    let path = url.path();
+    if path.len() > 1 && path.ends_with('/') {
+        let trimmed = path.trim_end_matches('/').to_string();
+        url.set_path(&trimmed);
+    }
+
+    url
+}
+
+/// An ID for the article at a (normalised) URL, used to find duplicate articles
+///
+/// This ignores the scheme and any `www.`, and if `collapse_scripts` is set, the segment of the
+/// path that picks the simplified or traditional version of the article, so both versions get
+/// the same ID
+pub fn article_id(url: &Url, collapse_scripts: bool) -> String {
+    let host = url.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+
+    let path = match url.path_segments() {
+        Some(segments) if collapse_scripts => segments
+            .filter(|segment| !SCRIPT_SEGMENTS.contains(segment))
+            .collect::<Vec<_>>()
+            .join("/"),
+        _ => url.path().trim_start_matches('/').to_string(),
+    };
+
+    match url.query() {
+        Some(query) => format!("{host}/{path}?{query}"),
+        None => format!("{host}/{path}"),
+    }
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    TRACKING_PARAMS.contains(&name)
+        || TRACKING_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_and_normalises_links() {
+        let base = Url::parse("https://www.bbc.com/zhongwen/simp/topics/c1?page=2").unwrap();
+        let canonical = |href| canonicalise(&base, href).map(String::from);
+
+        assert_eq!(
+            canonical("/zhongwen/simp/articles/c4n?at_medium=RSS&at_campaign=KARANGA#comments"),
+            Some("https://www.bbc.com/zhongwen/simp/articles/c4n".into())
+        );
+        assert_eq!(
+            canonical("../articles/c4n/"),
+            Some("https://www.bbc.com/zhongwen/simp/articles/c4n".into())
+        );
+        assert_eq!(
+            canonical("HTTPS://WWW.BBC.COM:443/zhongwen/simp/world-1?b=2&utm_source=x&a=1"),
+            Some("https://www.bbc.com/zhongwen/simp/world-1?a=1&b=2".into())
+        );
+        assert_eq!(canonical("mailto:someone@bbc.co.uk"), None);
+    }
+
+    #[test]
+    fn article_ids_can_ignore_script() {
+        let simp = Url::parse("https://www.bbc.com/zhongwen/simp/world-66414696").unwrap();
+        let trad = Url::parse("http://bbc.com/zhongwen/trad/world-66414696").unwrap();
+
+        assert_ne!(article_id(&simp, false), article_id(&trad, false));
+        assert_eq!(article_id(&simp, true), article_id(&trad, true));
+        assert_eq!(article_id(&simp, true), "bbc.com/zhongwen/world-66414696");
+
+        let simp = Url::parse("https://www.bbc.com/zhongwen/articles/c2/simp").unwrap();
+        let trad = Url::parse("https://www.bbc.com/zhongwen/articles/c2/trad").unwrap();
+        assert_eq!(article_id(&simp, true), article_id(&trad, true));
+    }
+}
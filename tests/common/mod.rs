@@ -0,0 +1,588 @@
+//! An in-process mock of every external API, so the tests can run without network access or
+//! real credentials
+
+#![allow(dead_code)]
+
+use std::{
+    sync::{mpsc, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use encoding_rs::GBK;
+use wiremock::{
+    matchers::{body_partial_json, header, method, path, path_regex, query_param},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+/// The description in the `<meta>` tag of every canned BBC article
+pub const DESCRIPTION: &str = "中国经济增长放缓，政府宣布新的刺激措施。";
+
+/// The description of the canned article at `/zhongwen/simp/articles/edited`, after the first
+/// time it's fetched (when it has [`DESCRIPTION`])
+pub const EDITED_DESCRIPTION: &str = "中国经济增长明显放缓，政府宣布新的刺激措施。";
+
+/// The description of the canned article at `/zhongwen/trad/articles/traditional`, which is
+/// [`DESCRIPTION`] in traditional characters
+pub const TRADITIONAL_DESCRIPTION: &str = "中國經濟增長放緩，政府宣布新的刺激措施。";
+
+/// The description of the canned article at `/news/articles/english`, an English page that
+/// slipped into a list of Chinese ones
+pub const ENGLISH_DESCRIPTION: &str =
+    "China's economy grew more slowly than expected, as the government announced new measures.";
+
+/// The description of the canned article at `/news/articles/russian`, from BBC Russian
+pub const RUSSIAN_DESCRIPTION: &str =
+    "Экономический рост в Китае замедлился, правительство объявило о новых мерах.";
+
+/// The first paragraph of every canned BBC article
+pub const PARAGRAPH: &str = "中国政府周一宣布了一系列新的经济刺激措施。";
+
+/// The `<h1>` of every canned BBC article
+pub const HEADLINE: &str = "中国经济增长放缓";
+
+/// The `article:section` of every canned BBC article
+pub const SECTION: &str = "中国";
+
+/// The translation returned by the mock Google Translate API
+pub const GOOGLE_TRANSLATION: &str =
+    "China's economic growth slowed, and the government announced new stimulus measures.";
+
+/// The translation returned by the mock Google Translate API for traditional Chinese (`zh-TW`)
+pub const TRADITIONAL_GOOGLE_TRANSLATION: &str =
+    "China's economic growth has slowed, and the government has announced new stimulus measures.";
+
+/// The translation returned by the mock ChatGPT API
+pub const CHATGPT_TRANSLATION: &str =
+    "China's economic growth slows as the government unveils fresh stimulus";
+
+/// The word that mock Google Trends responds to with `429 Too Many Requests` the first time
+pub const THROTTLED_WORD: &str = "throttled";
+
+/// The mock BBC's `robots.txt`, which disallows everything under [`DISALLOWED_PATH`]
+pub const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /zhongwen/simp/private/\n";
+
+/// A path that the mock BBC serves articles under, but its `robots.txt` disallows
+pub const DISALLOWED_PATH: &str = "/zhongwen/simp/private/";
+
+static MOCK_APIS: OnceLock<MockApis> = OnceLock::new();
+
+/// A local HTTP server that stands in for the BBC, OpenAI, Google Translate and Google Trends
+pub struct MockApis {
+    base_url: String,
+}
+
+impl MockApis {
+    /// Start the mock server (once per test binary), and point this process at it
+    ///
+    /// The server runs on its own thread and runtime, so it outlives the runtime of whichever test
+    /// happened to start it
+    pub fn start() -> &'static MockApis {
+        MOCK_APIS.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel();
+
+            thread::spawn(move || {
+                let runtime = tokio::runtime::Runtime::new().unwrap();
+                runtime.block_on(async {
+                    let server = MockServer::start().await;
+                    mount(&server).await;
+                    sender.send(server.uri()).unwrap();
+
+                    std::future::pending::<()>().await;
+                });
+            });
+
+            let mock = MockApis {
+                base_url: receiver.recv().unwrap(),
+            };
+
+            for (name, value) in mock.env() {
+                std::env::set_var(name, value);
+            }
+
+            mock
+        })
+    }
+
+    /// The URL of a path on the mock server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// The environment variables that point the program at this server, with fake credentials
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("OPENAI_KEY", "test-openai-key".to_string()),
+            ("GCLOUD_KEY", "test-gcloud-key".to_string()),
+            ("GCLOUD_PROJECT_ID", "test-project".to_string()),
+            ("GCLOUD_ACCESS_TOKEN", "test-access-token".to_string()),
+            ("OPENAI_BASE_URL", self.url("/v1")),
+            ("GOOGLE_TRANSLATE_BASE_URL", self.url("/v3beta1")),
+            ("TRENDS_BASE_URL", self.url("/trends/v1beta")),
+        ]
+    }
+}
+
+/// The Google Trends score the mock server gives to a word
+pub fn trends_score(word: &str) -> f64 {
+    (word.len() * 10 % 100) as f64
+}
+
+async fn mount(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": format!("\"{CHATGPT_TRANSLATION}\"") } }],
+        })))
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/v3beta1/projects/[^/]+:translateText$"))
+        .and(body_partial_json(
+            serde_json::json!({ "sourceLanguageCode": "zh-TW" }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "translations": [{ "translatedText": TRADITIONAL_GOOGLE_TRANSLATION }],
+        })))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/v3beta1/projects/[^/]+:translateText$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "translations": [{ "translatedText": GOOGLE_TRANSLATION }],
+        })))
+        .mount(server)
+        .await;
+
+    // the first request for this word is rejected, to test that we back off and retry
+    Mock::given(method("GET"))
+        .and(path("/trends/v1beta/graph"))
+        .and(query_param("terms", THROTTLED_WORD))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "1"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/trends/v1beta/graph"))
+        .respond_with(|request: &Request| {
+            let (_, word) = request
+                .url
+                .query_pairs()
+                .find(|(k, _)| k == "terms")
+                .unwrap();
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "lines": [{ "points": [{ "value": 0.0 }, { "value": trends_score(&word) }] }],
+            }))
+        })
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/zhongwen/simp/topics/broken$"))
+        .respond_with(ResponseTemplate::new(500))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    // a topic page that takes longer to respond than the tests are willing to wait
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/zhongwen/simp/topics/slow$"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/zhongwen/simp/topics/[^/]+$"))
+        .respond_with(|request: &Request| {
+            let topic = request.url.path().rsplit('/').next().unwrap();
+            let page = request
+                .url
+                .query_pairs()
+                .find(|(k, _)| k == "page")
+                .map_or(1, |(_, v)| v.parse().unwrap());
+            html(topic_html(topic, page))
+        })
+        .with_priority(2)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/z/\d+$"))
+        .respond_with(|request: &Request| {
+            let page = request
+                .url
+                .query_pairs()
+                .find(|(k, _)| k == "p")
+                .map_or(0, |(_, v)| v.parse().unwrap());
+            html(voa_section_html(page))
+        })
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/a/[^/]+/\d+\.html$"))
+        .respond_with(html(article_html(Some(DESCRIPTION), Some(PARAGRAPH))))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/robots.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(ROBOTS_TXT))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/sitemaps/index.xml"))
+        .respond_with(xml(sitemap_index(&server.uri())))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/sitemaps/news.xml"))
+        .respond_with(xml(news_sitemap(&server.uri())))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/feeds/rss.xml"))
+        .respond_with(xml(rss_feed(&server.uri())))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/feeds/atom.xml"))
+        .respond_with(xml(atom_feed(&server.uri())))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/no-text$"))
+        .respond_with(html(article_html(None, None)))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    for (name, description) in [
+        ("english", ENGLISH_DESCRIPTION),
+        ("russian", RUSSIAN_DESCRIPTION),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/news/articles/{name}")))
+            .respond_with(html(article_html(Some(description), None)))
+            .with_priority(1)
+            .mount(server)
+            .await;
+    }
+
+    // an older site, which only says that the page is in GBK in a `<meta>` tag
+    let gbk = article_html(Some(DESCRIPTION), Some(PARAGRAPH)).replace("utf-8", "gbk");
+    Mock::given(method("GET"))
+        .and(path_regex(r"/gbk$"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(GBK.encode(&gbk).0, "text/html"))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    // an article that says when it was published, on the day at the end of its path
+    Mock::given(method("GET"))
+        .and(path_regex(r"/dated/\d{4}-\d{2}-\d{2}$"))
+        .respond_with(|request: &Request| {
+            let date = request.url.path().rsplit('/').next().unwrap();
+            let time = format!(r#"<main><time datetime="{date}T09:00:00+08:00"></time>"#);
+            html(article_html(Some(DESCRIPTION), Some(PARAGRAPH)).replace("<main>", &time))
+        })
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    // a page with an `ETag`, which is only sent once, and afterwards only says it hasn't changed
+    // when asked with that `ETag`
+    Mock::given(method("GET"))
+        .and(path_regex(r"/etag$"))
+        .and(header("if-none-match", r#""v1""#))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/etag$"))
+        .respond_with(
+            html(article_html(Some(DESCRIPTION), Some(PARAGRAPH))).insert_header("etag", r#""v1""#),
+        )
+        .up_to_n_times(1)
+        .with_priority(2)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/etag$"))
+        .respond_with(ResponseTemplate::new(500))
+        .with_priority(3)
+        .mount(server)
+        .await;
+
+    // a page whose description is edited after it's first fetched
+    Mock::given(method("GET"))
+        .and(path_regex(r"/edited$"))
+        .respond_with(html(article_html(Some(DESCRIPTION), Some(PARAGRAPH))))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/edited$"))
+        .respond_with(html(article_html(
+            Some(EDITED_DESCRIPTION),
+            Some(PARAGRAPH),
+        )))
+        .with_priority(2)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/traditional$"))
+        .respond_with(html(article_html(Some(TRADITIONAL_DESCRIPTION), None)))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/no-description$"))
+        .respond_with(html(article_html(None, Some(PARAGRAPH))))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/(news|zhongwen)/"))
+        .respond_with(html(article_html(Some(DESCRIPTION), Some(PARAGRAPH))))
+        .mount(server)
+        .await;
+}
+
+/// The days the articles in the `archive` mock topic were published on
+pub const ARCHIVE_DATES: [&str; 3] = ["2023-01-05", "2023-01-20", "2023-02-10"];
+
+/// The number of pages in the `paged` and `unpaged` mock topics
+///
+/// Only `paged` has a pagination control. Asking `unpaged` for a page past the end gives the
+/// last page again
+pub const TOPIC_PAGES: usize = 3;
+
+/// The article URLs listed on a page of a mock topic
+///
+/// Each page lists two articles of its own, and one article that is on every page of every topic,
+/// except `bilingual`, which lists the simplified and traditional versions of one article, and
+/// `archive`, which lists [`ARCHIVE_DATES`] articles that say when they were published
+pub fn topic_articles(topic: &str, page: usize) -> Vec<String> {
+    let page = match topic {
+        "unpaged" => page.min(TOPIC_PAGES),
+        "archive" => {
+            let mut articles: Vec<_> = ARCHIVE_DATES
+                .iter()
+                .map(|date| format!("/zhongwen/simp/articles/dated/{date}"))
+                .collect();
+            articles.push("/zhongwen/simp/articles/everywhere".to_string());
+            return articles;
+        }
+        "bilingual" => {
+            return vec![
+                format!("/zhongwen/simp/articles/bilingual-{page}"),
+                format!("/zhongwen/trad/articles/bilingual-{page}"),
+            ]
+        }
+        _ => page,
+    };
+
+    vec![
+        format!("/zhongwen/simp/articles/{topic}-{page}-a"),
+        format!("/zhongwen/simp/articles/{topic}-{page}-b"),
+        "/zhongwen/simp/articles/everywhere".to_string(),
+    ]
+}
+
+fn topic_html(topic: &str, page: usize) -> String {
+    let articles = topic_articles(topic, page);
+
+    // the first article is linked to again with tracking parameters and a fragment, which
+    // shouldn't make it a different article
+    let tracked = format!("{}?at_medium=RSS&at_campaign=KARANGA#comments", articles[0]);
+
+    let links: String = articles
+        .iter()
+        .chain([&tracked])
+        .map(|href| format!(r#"<li><a class="bbc-uk8dsi" href="{href}">标题</a></li>"#))
+        .collect();
+
+    let pagination: String = match topic {
+        "paged" => (1..=TOPIC_PAGES)
+            .map(|i| format!(r#"<a href="?page={i}">{i}</a>"#))
+            .collect(),
+        _ => String::new(),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="zh-Hans">
+  <body>
+    <main>
+      <ul>{links}</ul>
+      <nav aria-label="Page">{pagination}</nav>
+    </main>
+  </body>
+</html>"#
+    )
+}
+
+/// The number of pages in the mock VOA section, which (like VOA's sections) has no pagination
+/// control, and numbers its pages from 0. Asking for a page past the end gives the last page again
+pub const VOA_SECTION_PAGES: usize = 2;
+
+/// The article URLs listed on a page of the mock VOA section, numbered from 0
+pub fn voa_section_articles(page: usize) -> Vec<String> {
+    let page = page.min(VOA_SECTION_PAGES - 1);
+    (0..2)
+        .map(|i| format!("/a/story-{page}-{i}/72123{page}{i}.html"))
+        .collect()
+}
+
+fn voa_section_html(page: usize) -> String {
+    let links: String = voa_section_articles(page)
+        .iter()
+        .map(|href| format!(r#"<li><a href="{href}">标题</a></li>"#))
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="zh-Hans">
+  <body>
+    <header><a href="/z/1739">中国</a></header>
+    <main>
+      <ul>{links}</ul>
+      <a href="?p={}">加载更多</a>
+    </main>
+  </body>
+</html>"#,
+        page + 1
+    )
+}
+
+/// The articles in the mock feeds, and when they were published (in the form the feeds are
+/// normalised to)
+///
+/// The news sitemap lists the first two, the RSS feed lists the second two, and the Atom feed lists
+/// the last one
+pub const FEED_ARTICLES: [(&str, &str); 4] = [
+    ("/zhongwen/simp/articles/sitemap-a", "2023-08-07"),
+    ("/zhongwen/simp/articles/shared", "2023-08-08T01:00:00Z"),
+    ("/zhongwen/simp/articles/rss-a", "2023-08-09T09:30:00Z"),
+    ("/zhongwen/simp/articles/atom-a", "2023-08-10T09:30:00Z"),
+];
+
+fn sitemap_index(base: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>{base}/sitemaps/news.xml</loc></sitemap>
+</sitemapindex>"#
+    )
+}
+
+fn news_sitemap(base: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+    xmlns:news="http://www.google.com/schemas/sitemap-news/0.9">
+  <url>
+    <loc>{base}/zhongwen/simp/articles/sitemap-a</loc>
+    <news:news><news:publication_date>2023-08-07</news:publication_date></news:news>
+  </url>
+  <url>
+    <loc>{base}/zhongwen/simp/articles/shared</loc>
+  </url>
+  <url>
+    <loc>{base}/zhongwen/simp/topics/economy</loc>
+  </url>
+</urlset>"#
+    )
+}
+
+fn rss_feed(base: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <title>BBC News 中文</title>
+    <link>{base}/zhongwen/simp</link>
+    <item>
+      <link>{base}/zhongwen/simp/articles/shared</link>
+      <pubDate>Tue, 08 Aug 2023 09:00:00 +0800</pubDate>
+    </item>
+    <item>
+      <link>{base}/zhongwen/simp/articles/rss-a?at_medium=RSS&amp;at_campaign=KARANGA</link>
+      <pubDate>Wed, 09 Aug 2023 09:30:00 GMT</pubDate>
+    </item>
+  </channel>
+</rss>"#
+    )
+}
+
+fn atom_feed(base: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <entry>
+    <link href="{base}/zhongwen/simp/articles/atom-a"/>
+    <published>2023-08-10T17:30:00+08:00</published>
+  </entry>
+</feed>"#
+    )
+}
+
+fn article_html(description: Option<&str>, paragraph: Option<&str>) -> String {
+    let body = match paragraph {
+        Some(paragraph) => format!("<p>{paragraph}</p>"),
+        None => String::new(),
+    };
+
+    let meta = match description {
+        Some(description) => format!(r#"<meta name="description" content="{description}">"#),
+        None => String::new(),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="zh-Hans">
+  <head>
+    <meta charset="utf-8">
+    <title>中国经济 - BBC News 中文</title>
+    <meta property="article:section" content="{SECTION}">
+    <meta property="og:title" content="{HEADLINE}">
+    {meta}
+    <script type="application/ld+json">{{"@type":"NewsArticle","headline":"{HEADLINE}"}}</script>
+  </head>
+  <body>
+    <main>
+      <h1>{HEADLINE}</h1>
+      {body}
+    </main>
+  </body>
+</html>"#
+    )
+}
+
+fn html(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(body, "text/html; charset=utf-8")
+}
+
+fn xml(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(body, "application/xml; charset=utf-8")
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{MockApis, DESCRIPTION, DISALLOWED_PATH, PARAGRAPH};
+use dissertation::html::{description_of_page, SelectorProfile};
+
+#[tokio::test]
+async fn bbc_article_has_desc() {
+    let mock = MockApis::start();
+
+    let url = mock.url("/news/world-asia-66414696");
+    let description = description_of_page(&url, &SelectorProfile::default())
+        .await
+        .unwrap();
+
+    assert_eq!(description, DESCRIPTION);
+}
+
+#[tokio::test]
+async fn missing_desc_falls_back_to_first_paragraph() {
+    let mock = MockApis::start();
+
+    let url = mock.url("/zhongwen/simp/articles/no-description");
+    let description = description_of_page(&url, &SelectorProfile::default())
+        .await
+        .unwrap();
+
+    assert_eq!(description, PARAGRAPH);
+}
+
+#[tokio::test]
+async fn missing_desc_is_an_error() {
+    let mock = MockApis::start();
+
+    let url = mock.url("/zhongwen/simp/articles/no-text");
+    let result = description_of_page(&url, &SelectorProfile::default()).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn skips_pages_disallowed_by_robots_txt() {
+    let mock = MockApis::start();
+
+    let url = mock.url(&format!("{DISALLOWED_PATH}c1"));
+    let error = description_of_page(&url, &SelectorProfile::default())
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("disallowed by robots.txt"));
+}
+
+#[tokio::test]
+async fn decodes_pages_that_arent_utf_8() {
+    let mock = MockApis::start();
+
+    let url = mock.url("/zhongwen/simp/articles/gbk");
+    let description = description_of_page(&url, &SelectorProfile::default())
+        .await
+        .unwrap();
+
+    assert_eq!(description, DESCRIPTION);
+}
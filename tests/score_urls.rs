@@ -0,0 +1,453 @@
+mod common;
+
+use std::{collections::HashMap, fs, path::Path, process::Command};
+
+use common::{
+    MockApis, CHATGPT_TRANSLATION, DESCRIPTION, EDITED_DESCRIPTION, GOOGLE_TRANSLATION, HEADLINE,
+    PARAGRAPH, RUSSIAN_DESCRIPTION, SECTION, TRADITIONAL_DESCRIPTION,
+    TRADITIONAL_GOOGLE_TRANSLATION,
+};
+
+const PROMPTS: &str = r#"{
+  american_english: { text: "Translate into American English: {text}", region: "america" },
+  british_english: { text: "Translate into British English: {text}", region: "britain" },
+}"#;
+
+type Row = HashMap<String, String>;
+
+/// Run `score_urls` against the mock APIs, returning the CSV header, the rows (keyed by column)
+/// and anything written to stderr
+fn score_urls(
+    mock: &MockApis,
+    dir: &Path,
+    url_list: &str,
+    args: &[&str],
+) -> (Vec<String>, Vec<Row>, String) {
+    let env: String = mock
+        .env()
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}\n"))
+        .collect();
+    fs::write(dir.join(".env"), env).unwrap();
+    fs::write(dir.join("urls.txt"), url_list).unwrap();
+    fs::write(dir.join("prompts.json5"), PROMPTS).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_score_urls"))
+        .current_dir(dir)
+        .args(["--urls", "urls.txt"])
+        .args(["--prompts", "prompts.json5"])
+        .args(["--output", "out.csv"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let mut reader = csv::Reader::from_path(dir.join("out.csv")).unwrap();
+    let header = reader.headers().unwrap().iter().map(String::from).collect();
+    let rows = reader.deserialize().map(Result::unwrap).collect();
+
+    (header, rows, String::from_utf8(output.stderr).unwrap())
+}
+
+fn assert_scored(row: &Row) {
+    assert_eq!(row["google"], GOOGLE_TRANSLATION);
+    assert_eq!(row["american_english"], CHATGPT_TRANSLATION);
+    assert_eq!(row["british_english"], CHATGPT_TRANSLATION);
+
+    for column in [
+        "google_us_score",
+        "google_uk_score",
+        "american_english_score",
+        "british_english_score",
+    ] {
+        assert!(row[column].parse::<f64>().is_ok(), "{column} isn't a score");
+    }
+}
+
+/// Run the whole `score_urls` pipeline against the mock APIs
+#[test]
+fn scores_urls_end_to_end() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let urls = [
+        mock.url("/zhongwen/simp/articles/c1"),
+        mock.url("/zhongwen/simp/articles/no-description"),
+        mock.url("/zhongwen/simp/articles/no-text"),
+    ];
+    // the first URL has a publication date, as if it came from a feed
+    let url_list = format!("{}\t2023-08-07\n{}\n{}", urls[0], urls[1], urls[2]);
+
+    let (header, rows, stderr) = score_urls(mock, dir.path(), &url_list, &[]);
+
+    assert_eq!(
+        header,
+        [
+            "url",
+            "source",
+            "topic",
+            "category",
+            "unit",
+            "unit_index",
+            "canonical_url",
+            "headline",
+            "published",
+            "section",
+            "author",
+            "page_title",
+            "og_title",
+            "og_description",
+            "twitter_description",
+            "description_source",
+            "news_article",
+            "description_version",
+            "script",
+            "language",
+            "source_text",
+            "google",
+            "american_english",
+            "british_english",
+            "google_us_score",
+            "google_uk_score",
+            "american_english_score",
+            "british_english_score",
+        ]
+    );
+    assert_eq!(rows.len(), 3);
+
+    let scored = &rows[0];
+    assert_eq!(scored["url"], urls[0]);
+    assert_eq!(scored["source"], "bbc");
+    assert_eq!(scored["unit"], "description");
+    assert_eq!(scored["unit_index"], "0");
+    assert_eq!(scored["canonical_url"], "");
+    assert_eq!(scored["headline"], HEADLINE);
+    assert_eq!(scored["published"], "2023-08-07");
+    assert_eq!(scored["section"], SECTION);
+    assert_eq!(scored["author"], "");
+    assert_eq!(scored["page_title"], "中国经济 - BBC News 中文");
+    assert_eq!(scored["og_title"], HEADLINE);
+    assert_eq!(scored["og_description"], "");
+    assert_eq!(
+        scored["news_article"],
+        format!(r#"{{"@type":"NewsArticle","headline":"{HEADLINE}"}}"#)
+    );
+    assert_eq!(scored["description_source"], "meta");
+    assert_eq!(scored["script"], "simplified");
+    assert_eq!(scored["language"], "cmn");
+    assert_eq!(scored["source_text"], DESCRIPTION);
+    assert_scored(scored);
+
+    // a page without a description tag falls back to its first paragraph, and says so
+    let fallback = &rows[1];
+    assert_eq!(fallback["url"], urls[1]);
+    assert_eq!(fallback["description_source"], "paragraph");
+    assert_eq!(fallback["source_text"], PARAGRAPH);
+    assert_scored(fallback);
+
+    // a page without any text still gets a row with its metadata, but nothing else
+    let missing = &rows[2];
+    assert_eq!(missing["url"], urls[2]);
+    assert_eq!(missing["headline"], HEADLINE);
+    assert_eq!(missing["unit"], "");
+    assert_eq!(missing["script"], "");
+    assert_eq!(missing["source_text"], "");
+    assert_eq!(missing["google_us_score"], "");
+
+    assert!(stderr.contains("1 of 3 pages had no description"));
+    assert!(stderr.contains("/zhongwen/simp/articles/no-text"));
+}
+
+#[test]
+fn scores_each_paragraph() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = mock.url("/zhongwen/simp/articles/c1");
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &["--unit", "paragraph"]);
+
+    let units: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            assert_eq!(row["url"], url);
+            assert_scored(row);
+
+            (
+                row["unit"].as_str(),
+                row["unit_index"].as_str(),
+                row["source_text"].as_str(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        units,
+        [("heading", "0", HEADLINE), ("paragraph", "1", PARAGRAPH)]
+    );
+}
+
+#[test]
+fn scores_headlines() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = mock.url("/zhongwen/simp/articles/c1");
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &["--unit", "headline"]);
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["unit"], "headline");
+    assert_eq!(rows[0]["source_text"], HEADLINE);
+    assert_scored(&rows[0]);
+}
+
+#[test]
+fn rescores_snapshots_offline() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let archived = mock.url("/zhongwen/simp/articles/archived");
+    let (_, rows, _) = score_urls(mock, dir.path(), &archived, &["--snapshots", "pages.db"]);
+    assert_eq!(rows[0]["source_text"], DESCRIPTION);
+
+    // offline, the archived page is read from the snapshot, and the other page isn't fetched,
+    // even though the mock server would serve it
+    let unarchived = mock.url("/zhongwen/simp/articles/unarchived");
+    let url_list = format!("{archived}\n{unarchived}");
+    let args = ["--snapshots", "pages.db", "--offline"];
+    let (_, rows, stderr) = score_urls(mock, dir.path(), &url_list, &args);
+
+    assert_eq!(rows[0]["url"], archived);
+    assert_eq!(rows[0]["source_text"], DESCRIPTION);
+    assert_scored(&rows[0]);
+
+    assert_eq!(rows[1]["url"], unarchived);
+    assert_eq!(rows[1]["source_text"], "");
+    assert!(stderr.contains("1 of 2 pages had no description"));
+}
+
+#[test]
+fn reads_pages_with_the_source_outlets_selectors() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = mock.url("/a/story-0-0/7212300.html");
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &["--source", "voa"]);
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["source"], "voa");
+    assert_eq!(rows[0]["source_text"], DESCRIPTION);
+    assert_scored(&rows[0]);
+}
+
+#[test]
+fn detects_and_converts_scripts() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = mock.url("/zhongwen/trad/articles/traditional");
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &[]);
+
+    // traditional text is translated as `zh-TW`
+    assert_eq!(rows[0]["script"], "traditional");
+    assert_eq!(rows[0]["source_text"], TRADITIONAL_DESCRIPTION);
+    assert_eq!(rows[0]["google"], TRADITIONAL_GOOGLE_TRANSLATION);
+
+    // converted to simplified first, the converted text is what gets translated (as `zh-CN`),
+    // but the `script` column still says what was on the page
+    let args = ["--convert-script", "simplified"];
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &args);
+
+    assert_eq!(rows[0]["script"], "traditional");
+    assert_eq!(rows[0]["source_text"], DESCRIPTION);
+    assert_scored(&rows[0]);
+}
+
+#[test]
+fn flags_texts_in_other_languages() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let urls = [
+        mock.url("/zhongwen/simp/articles/c1"),
+        mock.url("/news/articles/english"),
+    ];
+    let url_list = urls.join("\n");
+    let (_, rows, stderr) = score_urls(mock, dir.path(), &url_list, &[]);
+
+    assert_scored(&rows[0]);
+
+    // the English page is identified, but not translated
+    assert_eq!(rows[1]["unit"], "description");
+    assert_eq!(rows[1]["language"], "eng");
+    assert_eq!(rows[1]["google"], "");
+    assert_eq!(rows[1]["google_us_score"], "");
+
+    assert!(stderr.contains("1 of 2 texts weren't in Chinese"));
+    assert!(stderr.contains(&format!("{} (English)", urls[1])));
+}
+
+#[test]
+fn translates_from_other_languages() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let url = mock.url("/news/articles/russian");
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &["--language", "russian"]);
+
+    assert_eq!(rows[0]["language"], "rus");
+    assert_eq!(rows[0]["script"], "");
+    assert_eq!(rows[0]["source_text"], RUSSIAN_DESCRIPTION);
+    assert_scored(&rows[0]);
+
+    // the same page is flagged when the pages are meant to be in Chinese
+    let (_, rows, _) = score_urls(mock, dir.path(), &url, &[]);
+    assert_eq!(rows[0]["language"], "rus");
+    assert_eq!(rows[0]["google"], "");
+}
+
+#[test]
+fn writes_topic_and_category() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    // as written by `scrape_bbc` from a structured topics file, without a publication date
+    let url = mock.url("/zhongwen/simp/articles/c1");
+    let url_list = format!("{url}\t\teconomy\tbusiness");
+    let (_, rows, _) = score_urls(mock, dir.path(), &url_list, &[]);
+
+    assert_eq!(rows[0]["topic"], "economy");
+    assert_eq!(rows[0]["category"], "business");
+    assert_eq!(rows[0]["published"], "");
+    assert_scored(&rows[0]);
+}
+
+#[test]
+fn filters_pages_by_publication_date() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let dated = |date: &str| mock.url(&format!("/zhongwen/simp/articles/dated/{date}"));
+    let undated = mock.url("/zhongwen/simp/articles/c1");
+    // the date on the page outranks the one in the URL list, which is used for pages without one
+    let url_list = [
+        format!("{}\t2023-03-01", dated("2023-01-05")),
+        dated("2023-01-20"),
+        dated("2023-02-10"),
+        format!("{}\t2023-02-01", mock.url("/zhongwen/simp/articles/c2")),
+        undated.clone(),
+    ]
+    .join("\n");
+
+    let args = ["--since", "2023-01-10", "--until", "2023-02-05"];
+    let (_, rows, stderr) = score_urls(mock, dir.path(), &url_list, &args);
+
+    let published: Vec<_> = rows.iter().map(|row| row["published"].as_str()).collect();
+    assert_eq!(published, ["2023-01-20T09:00:00+08:00", "2023-02-01"]);
+    assert_scored(&rows[0]);
+    assert!(stderr.contains(&format!(
+        "1 of 5 pages had no publication date, so were left out:\n  {undated}"
+    )));
+
+    // one page from each month
+    let (_, rows, _) = score_urls(mock, dir.path(), &url_list, &["--per-month", "1"]);
+    let urls: Vec<_> = rows.iter().map(|row| row["url"].clone()).collect();
+    assert_eq!(urls, [dated("2023-01-20"), dated("2023-02-10")]);
+}
+
+#[test]
+fn drops_near_duplicate_descriptions() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    // every mock article has the same description, except the traditional one
+    let urls = [
+        mock.url("/zhongwen/simp/articles/c1"),
+        mock.url("/zhongwen/trad/articles/traditional"),
+        mock.url("/zhongwen/simp/articles/c2"),
+        mock.url("/zhongwen/simp/articles/c3"),
+    ];
+    let args = ["--drop-duplicates", "--duplicates", "duplicates.csv"];
+    let (_, rows, stderr) = score_urls(mock, dir.path(), &urls.join("\n"), &args);
+
+    let scored: Vec<_> = rows.iter().map(|row| row["url"].as_str()).collect();
+    assert_eq!(scored, [&urls[0], &urls[1]]);
+    assert!(stderr.contains("3 of 4 pages had near-duplicate descriptions, in 1 clusters:"));
+
+    let report = fs::read_to_string(dir.path().join("duplicates.csv")).unwrap();
+    let expected = [
+        "cluster,representative,url,description".to_string(),
+        format!("1,true,{},{DESCRIPTION}", urls[0]),
+        format!("1,false,{},{DESCRIPTION}", urls[2]),
+        format!("1,false,{},{DESCRIPTION}", urls[3]),
+    ];
+    assert_eq!(report.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn tracks_changes_to_refetched_pages() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let unchanged = mock.url("/zhongwen/simp/articles/etag");
+    let edited = mock.url("/zhongwen/simp/articles/edited");
+    let url_list = format!("{unchanged}\n{edited}");
+    let args = ["--snapshots", "pages.db"];
+
+    let (_, rows, _) = score_urls(mock, dir.path(), &url_list, &args);
+    for row in &rows {
+        assert_eq!(row["source_text"], DESCRIPTION);
+        assert_eq!(row["description_version"], "1");
+    }
+
+    // the unchanged page is answered with `304 Not Modified` and no body, so its description
+    // comes from the snapshot
+    let (_, rows, stderr) = score_urls(mock, dir.path(), &url_list, &args);
+
+    assert_eq!(rows[0]["source_text"], DESCRIPTION);
+    assert_eq!(rows[0]["description_version"], "1");
+    assert_scored(&rows[0]);
+
+    assert_eq!(rows[1]["source_text"], EDITED_DESCRIPTION);
+    assert_eq!(rows[1]["description_version"], "2");
+    assert!(stderr.contains(&format!(
+        "1 of 2 pages had a different description from when they were last fetched:\n  {edited} (now version 2)"
+    )));
+}
+
+#[test]
+fn replays_a_cassette_and_fails_on_a_miss() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let recorded = mock.url("/zhongwen/simp/articles/c1");
+    let args = ["--cassette", "cassette.db", "--cassette-mode", "record"];
+    let (_, rows, _) = score_urls(mock, dir.path(), &recorded, &args);
+    assert_scored(&rows[0]);
+
+    let args = ["--cassette", "cassette.db", "--cassette-mode", "replay"];
+    let (_, replayed, _) = score_urls(mock, dir.path(), &recorded, &args);
+    assert_eq!(replayed, rows);
+
+    // a page that was never recorded means the run has diverged from the recording, so it fails
+    // rather than writing a row without a description
+    let unrecorded = mock.url("/zhongwen/simp/articles/unrecorded");
+    fs::write(
+        dir.path().join("urls.txt"),
+        format!("{recorded}\n{unrecorded}"),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_score_urls"))
+        .current_dir(dir.path())
+        .args(["--urls", "urls.txt"])
+        .args(["--prompts", "prompts.json5"])
+        .args(["--output", "out.csv"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("request not found in cassette"));
+    assert!(stderr.contains(&unrecorded));
+}
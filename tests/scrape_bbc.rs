@@ -0,0 +1,359 @@
+mod common;
+
+use std::{collections::HashSet, fs, process::Command};
+
+use common::{
+    topic_articles, voa_section_articles, MockApis, ARCHIVE_DATES, TOPIC_PAGES, VOA_SECTION_PAGES,
+};
+
+#[test]
+fn scrapes_topics_and_reports_failures() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let topics = [
+        format!("{} 2", mock.url("/zhongwen/simp/topics/economy")),
+        format!("{} 1", mock.url("/zhongwen/simp/topics/broken")),
+        format!("{} 1", mock.url("/zhongwen/simp/topics/science")),
+    ];
+    fs::write(dir.path().join("topics.txt"), topics.join("\n")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.txt"])
+        .args(["--output", "urls.txt"])
+        .args(["--retries", "0"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // the broken topic doesn't stop the others from being scraped
+    let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+    let urls: Vec<_> = urls.lines().collect();
+
+    // relative links are resolved against the topic page
+    let mut expected = topic_articles("economy", 1);
+    expected.extend(topic_articles("economy", 2));
+    expected.extend(topic_articles("science", 1));
+    let mut expected: Vec<_> = expected.iter().map(|path| mock.url(path)).collect();
+
+    // articles that appear on several pages are only written once
+    let mut seen = HashSet::new();
+    expected.retain(|url| seen.insert(url.clone()));
+
+    assert_eq!(urls, expected);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("1 of 4 pages failed"));
+    assert!(stderr.contains("/zhongwen/simp/topics/broken?page=1"));
+}
+
+#[test]
+fn discovers_number_of_pages() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let topics = [
+        mock.url("/zhongwen/simp/topics/paged"),
+        mock.url("/zhongwen/simp/topics/unpaged"),
+    ];
+    fs::write(dir.path().join("topics.txt"), topics.join("\n")).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.txt"])
+        .args(["--output", "urls.txt"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+    let urls: HashSet<_> = urls.lines().map(String::from).collect();
+
+    let expected: HashSet<_> = ["paged", "unpaged"]
+        .into_iter()
+        .flat_map(|topic| (1..=TOPIC_PAGES).flat_map(move |page| topic_articles(topic, page)))
+        .map(|path| mock.url(&path))
+        .collect();
+
+    assert_eq!(urls, expected);
+}
+
+#[test]
+fn collapses_simplified_and_traditional_articles() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let topics = format!("{} 1", mock.url("/zhongwen/simp/topics/bilingual"));
+    fs::write(dir.path().join("topics.txt"), topics).unwrap();
+
+    let scrape = |args: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+            .current_dir(dir.path())
+            .args(["--topics", "topics.txt"])
+            .args(["--output", "urls.txt"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        fs::read_to_string(dir.path().join("urls.txt")).unwrap()
+    };
+
+    let [simp, trad] = ["simp", "trad"]
+        .map(|script| mock.url(&format!("/zhongwen/{script}/articles/bilingual-1")));
+
+    assert_eq!(scrape(&[]).lines().collect::<Vec<_>>(), [&simp, &trad]);
+    assert_eq!(
+        scrape(&["--collapse-scripts"]).lines().collect::<Vec<_>>(),
+        [&simp]
+    );
+}
+
+#[test]
+fn scrapes_voa_sections() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(dir.path().join("topics.txt"), mock.url("/z/1739")).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.txt"])
+        .args(["--output", "urls.txt"])
+        .args(["--source", "voa"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // VOA's links and `?p=` pages are followed, and the section link in the header is ignored
+    let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+    let urls: Vec<_> = urls.lines().map(String::from).collect();
+
+    let expected: Vec<_> = (0..VOA_SECTION_PAGES)
+        .flat_map(voa_section_articles)
+        .map(|path| mock.url(&path))
+        .collect();
+
+    assert_eq!(urls, expected);
+}
+
+#[test]
+fn malformed_topics_are_reported_with_line_numbers() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let topics = "https://www.bbc.com/zhongwen/simp/topics/c1 2\n\nhttps://www.bbc.com/zhongwen/simp/topics/c2 lots";
+    fs::write(dir.path().join("topics.txt"), topics).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.txt"])
+        .args(["--output", "urls.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("topics.txt:3: expected a positive number of pages, found `lots`"));
+}
+
+#[test]
+fn scrapes_incrementally() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let scrape = |topics: &[String], args: &[&str]| {
+        fs::write(dir.path().join("topics.txt"), topics.join("\n")).unwrap();
+
+        let status = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+            .current_dir(dir.path())
+            .args(["--topics", "topics.txt"])
+            .args(["--output", "urls.txt"])
+            .args(["--seen", "seen.db"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+        urls.lines().map(String::from).collect::<Vec<_>>()
+    };
+    let urls = |paths: Vec<String>| -> Vec<_> { paths.iter().map(|path| mock.url(path)).collect() };
+
+    let economy = mock.url("/zhongwen/simp/topics/economy");
+    let science = mock.url("/zhongwen/simp/topics/science");
+
+    let first = scrape(&[format!("{economy} 1")], &[]);
+    assert_eq!(first, urls(topic_articles("economy", 1)));
+
+    // the first page of `economy` has nothing new, so its second page isn't scraped, and the
+    // article on every page isn't new either
+    let second = scrape(&[format!("{economy} 2"), format!("{science} 1")], &[]);
+    assert_eq!(second, urls(topic_articles("science", 1)[..2].to_vec()));
+
+    // everything found so far, in the order it was first found
+    let all = scrape(&[format!("{economy} 1")], &["--export", "all"]);
+    assert_eq!(all, [first, second].concat());
+}
+
+#[test]
+fn labels_articles_with_their_topic() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let topics = format!(
+        r#"[
+          {{ name: "economy", category: "business", url: "{}", pages: 1 }},
+          {{ name: "science", category: "tech", url: "{}", pages: 1, selectors: "science.json5" }},
+        ]"#,
+        mock.url("/zhongwen/simp/topics/economy"),
+        mock.url("/zhongwen/simp/topics/science"),
+    );
+    fs::write(dir.path().join("topics.json5"), topics).unwrap();
+    // only the first article on each page matches the science topic's own link pattern
+    let selectors = r#"{ link_patterns: ["-1-a"] }"#;
+    fs::write(dir.path().join("science.json5"), selectors).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.json5"])
+        .args(["--output", "urls.txt"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+    let urls: Vec<_> = urls.lines().collect();
+
+    let mut expected: Vec<_> = topic_articles("economy", 1)
+        .iter()
+        .map(|path| format!("{}\t\teconomy\tbusiness", mock.url(path)))
+        .collect();
+    expected.push(format!(
+        "{}\t\tscience\ttech",
+        mock.url(&topic_articles("science", 1)[0])
+    ));
+
+    assert_eq!(urls, expected);
+}
+
+#[test]
+fn follows_pages_with_the_topics_own_selectors() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    // without a pagination control, pages are followed one at a time
+    let topics = format!(
+        r#"[{{ url: "{}", selectors: "unpaged.json5" }}]"#,
+        mock.url("/zhongwen/simp/topics/unpaged"),
+    );
+    fs::write(dir.path().join("topics.json5"), topics).unwrap();
+    fs::write(
+        dir.path().join("unpaged.json5"),
+        r#"{ link_patterns: ["-a$"] }"#,
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.json5"])
+        .args(["--output", "urls.txt"])
+        .args(["--max-pages", "2"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+    let urls: Vec<_> = urls.lines().collect();
+
+    // only the first article on each page matches the topic's link pattern, on the second page
+    // as well as the first
+    let expected: Vec<_> = (1..=2)
+        .map(|page| mock.url(&topic_articles("unpaged", page)[0]))
+        .collect();
+    assert_eq!(urls, expected);
+}
+
+#[test]
+fn filters_articles_by_publication_date() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let topics = format!("{} 1", mock.url("/zhongwen/simp/topics/archive"));
+    fs::write(dir.path().join("topics.txt"), topics).unwrap();
+
+    let scrape = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+            .current_dir(dir.path())
+            .args(["--topics", "topics.txt"])
+            .args(["--output", "urls.txt"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+        let urls = urls.lines().map(String::from).collect::<Vec<_>>();
+        (urls, String::from_utf8(output.stderr).unwrap())
+    };
+    let dated = |date: &str| {
+        let url = mock.url(&format!("/zhongwen/simp/articles/dated/{date}"));
+        format!("{url}\t{date}T09:00:00+08:00")
+    };
+
+    let (urls, stderr) = scrape(&["--until", "2023-01-31"]);
+    assert_eq!(urls, [dated(ARCHIVE_DATES[0]), dated(ARCHIVE_DATES[1])]);
+
+    // the article on every topic page doesn't say when it was published
+    let everywhere = mock.url("/zhongwen/simp/articles/everywhere");
+    assert!(stderr.contains(&format!(
+        "1 of 4 articles had no publication date, so were left out:\n  {everywhere}"
+    )));
+
+    // one from each month, from the middle of January's two
+    let (urls, _) = scrape(&["--per-month", "1"]);
+    assert_eq!(urls, [dated(ARCHIVE_DATES[1]), dated(ARCHIVE_DATES[2])]);
+
+    // only the articles that are kept are added to the store, so a later run without the range
+    // still finds the rest
+    let (urls, _) = scrape(&["--until", "2023-01-31", "--seen", "seen.db"]);
+    assert_eq!(urls, [dated(ARCHIVE_DATES[0]), dated(ARCHIVE_DATES[1])]);
+
+    let (urls, _) = scrape(&["--seen", "seen.db"]);
+    let february = mock.url(&format!(
+        "/zhongwen/simp/articles/dated/{}",
+        ARCHIVE_DATES[2]
+    ));
+    assert_eq!(urls, [february, everywhere]);
+}
+
+#[test]
+fn reports_pages_that_time_out() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let topic = mock.url("/zhongwen/simp/topics/slow");
+    fs::write(dir.path().join("topics.txt"), format!("{topic} 1")).unwrap();
+    fs::write(
+        dir.path().join("config.json5"),
+        "{ http: { news: { timeout: 0.2 } } }",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_scrape_bbc"))
+        .current_dir(dir.path())
+        .args(["--topics", "topics.txt"])
+        .args(["--output", "urls.txt"])
+        .args(["--config", "config.json5"])
+        .args(["--retries", "0"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let page = format!("{topic}?page=1");
+    assert!(stderr.contains("1 of 1 pages failed:"));
+    assert!(stderr.contains(&format!(
+        "{page}: timed out waiting for bbc to respond to {page}"
+    )));
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{MockApis, FEED_ARTICLES};
+
+#[test]
+fn discovers_articles_from_sitemaps_and_feeds() {
+    let mock = MockApis::start();
+    let dir = tempfile::tempdir().unwrap();
+
+    let sources = [
+        "# sitemap indexes are followed".to_string(),
+        mock.url("/sitemaps/index.xml"),
+        mock.url("/feeds/rss.xml"),
+        mock.url("/feeds/atom.xml"),
+        mock.url("/feeds/missing.xml"),
+    ];
+    fs::write(dir.path().join("sources.txt"), sources.join("\n")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_scrape_feeds"))
+        .current_dir(dir.path())
+        .args(["--sources", "sources.txt"])
+        .args(["--output", "urls.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // the topic page in the sitemap isn't an article, and the article in both the sitemap and the
+    // RSS feed is only written once, with the date from the RSS feed
+    let urls = fs::read_to_string(dir.path().join("urls.txt")).unwrap();
+    let urls: Vec<_> = urls.lines().collect();
+
+    let expected: Vec<_> = FEED_ARTICLES
+        .iter()
+        .map(|(path, published)| format!("{}\t{published}", mock.url(path)))
+        .collect();
+
+    assert_eq!(urls, expected);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("1 of 4 sources failed"));
+    assert!(stderr.contains("/feeds/missing.xml"));
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::time::Instant;
+
+use common::{trends_score, MockApis, THROTTLED_WORD};
+use dissertation::scoring::{Region, Trends};
+use futures::future::try_join_all;
+
+#[tokio::test]
+async fn can_google_trends_concurrent() {
+    MockApis::start();
+
+    let dir = tempfile::tempdir().unwrap();
+    let trends = Trends::open(dir.path().join("trends.db"));
+
+    let strings: Vec<_> = ('a'..='z')
+        .flat_map(|c1| ('a'..='z').map(move |c2| format!("{c1}{c2}")))
+        .collect();
+
+    let scores = try_join_all(strings.iter().map(|s| trends.score(s, Region::Britain)))
+        .await
+        .unwrap();
+
+    assert!(scores.iter().all(|score| *score == trends_score("aa")));
+}
+
+#[tokio::test]
+async fn retries_after_too_many_requests() {
+    MockApis::start();
+
+    let dir = tempfile::tempdir().unwrap();
+    let trends = Trends::open(dir.path().join("trends.db"));
+
+    let start = Instant::now();
+    let score = trends.score(THROTTLED_WORD, Region::America).await.unwrap();
+
+    assert_eq!(score, trends_score(THROTTLED_WORD));
+    // the mock asks us to wait a second before trying again
+    assert!(start.elapsed().as_secs_f64() >= 1.0);
+}